@@ -1,34 +1,312 @@
-use crate::{JoinPlan, ROW_ID_COL_NAME};
-use anyhow::{Context, Result};
+use crate::{DerivedColumn, JoinPlan, Operand, Predicate, ROW_ID_COL_NAME};
+use anyhow::{ensure, Context, Result};
 use polars::prelude::*;
+use std::collections::HashSet;
 
 pub fn join<'a, 'b>(
-    df_fn: impl Fn(&'a str) -> Option<&'a DataFrame>,
+    df_fn: impl Fn(&str) -> Option<&'a DataFrame>,
     plan: &'b JoinPlan,
     is_assignment: bool,
 ) -> Result<DataFrame> {
+    join_schema(&df_fn, plan, is_assignment)?;
+
+    let mut table = if is_assignment {
+        join_with_assignment(df_fn, plan)?
+    } else {
+        join_without_assignment(df_fn, plan)?
+    };
+
+    derive(&mut table, &plan.derived)?;
+    filter(table, &plan.filters)
+}
+
+/// Append the derived columns to `df`, in order.
+fn derive(df: &mut DataFrame, columns: &[DerivedColumn]) -> Result<()> {
+    for column in columns {
+        let series = column.eval(df)?;
+        df.with_column(series)?;
+    }
+    Ok(())
+}
+
+/// Keep the rows satisfying all predicates.
+fn filter(df: DataFrame, predicates: &[Predicate]) -> Result<DataFrame> {
+    let mut mask: Option<BooleanChunked> = None;
+    for predicate in predicates {
+        let m = predicate.eval(&df)?;
+        mask = Some(match mask {
+            Some(mask) => mask & m,
+            None => m,
+        });
+    }
+
+    match mask {
+        Some(mask) => Ok(df.filter(&mask)?),
+        None => Ok(df),
+    }
+}
+
+/// Compute the schema of the join result without running the join.
+///
+/// It fails early if a table or a join key cannot be found, or if a pair of join keys
+/// have different dtypes, so that a bad plan is rejected before any expensive join runs.
+pub fn join_schema<'a, 'b>(
+    df_fn: impl Fn(&str) -> Option<&'a DataFrame>,
+    plan: &'b JoinPlan,
+    is_assignment: bool,
+) -> Result<Schema> {
+    let mut schema = df_fn(&plan.init_table)
+        .with_context(|| format!("cannot find init table {}", plan.init_table))?
+        .schema();
+
+    for (i, step) in plan.steps.iter().enumerate() {
+        let table_name = &step.table_to_join;
+        let right_schema = df_fn(table_name)
+            .with_context(|| format!("step #{i}: cannot find table {table_name} to join"))?
+            .schema();
+
+        for (l, r) in step.left_join_keys.iter().zip(step.right_join_keys.iter()) {
+            let l_dtype = schema.get(l).with_context(|| {
+                format!("step #{i}: cannot find left join key {l} to join {table_name}")
+            })?;
+            let r_dtype = right_schema.get(r).with_context(|| {
+                format!("step #{i}: cannot find right join key {r} in {table_name}")
+            })?;
+            ensure!(
+                l_dtype == r_dtype,
+                "step #{i}: join keys {l} ({l_dtype}) and {table_name}.{r} ({r_dtype}) have different dtypes"
+            );
+        }
+
+        let mut joined = schema.clone();
+        for (name, dtype) in right_schema.iter() {
+            if step.right_join_keys.contains(name) {
+                continue;
+            }
+            let name = if schema.get(name).is_some() {
+                format!("{name}:{table_name}")
+            } else {
+                name.clone()
+            };
+            joined.with_column(name, dtype.clone());
+        }
+        schema = joined;
+
+        if is_assignment {
+            for (l, r) in step.left_join_keys.iter().zip(step.right_join_keys.iter()) {
+                ensure!(
+                    l == r || schema.get(r).is_none(),
+                    "step #{i}: renaming join key {l} to {r} duplicates column {r}"
+                );
+                schema.rename(l, r.clone());
+            }
+        }
+    }
+
     if is_assignment {
-        join_with_assignment(df_fn, plan)
+        schema.rename(
+            ROW_ID_COL_NAME,
+            format!("{}:{}", ROW_ID_COL_NAME, plan.init_table),
+        );
+    }
+
+    for derived in &plan.derived {
+        let name = &derived.name;
+        let operand_dtype = |operand: &Operand| -> Result<DataType> {
+            Ok(match operand {
+                Operand::Column { column } => schema
+                    .get(column)
+                    .with_context(|| format!("cannot find column {column} to derive {name}"))?
+                    .clone(),
+                Operand::Int(_) => DataType::Int64,
+                Operand::Float(_) => DataType::Float64,
+                Operand::Str(_) => DataType::Utf8,
+            })
+        };
+
+        let dtype = operand_dtype(&derived.otherwise)?;
+        for case in &derived.cases {
+            for predicate in &case.when {
+                check_predicate(&schema, predicate)
+                    .with_context(|| format!("failed to derive column {name}"))?;
+            }
+            let case_dtype = operand_dtype(&case.then)?;
+            ensure!(
+                case_dtype == dtype,
+                "derived column {name}: cases have different dtypes ({case_dtype} and {dtype})"
+            );
+        }
+        ensure!(
+            schema.get(name).is_none(),
+            "derived column {name} duplicates an existing column"
+        );
+        schema.with_column(name.clone(), dtype);
+    }
+
+    for predicate in &plan.filters {
+        check_predicate(&schema, predicate)?;
+    }
+
+    for column in &plan.projection {
+        ensure!(
+            schema.get(column).is_some(),
+            "cannot find column {column} to project"
+        );
+    }
+
+    if !is_assignment {
+        let patterns = plan.owner_column_patterns()?;
+        for (pattern, re) in plan.owner_columns.iter().zip(patterns) {
+            ensure!(
+                schema.iter_names().any(|c| re.is_match(c)),
+                "owner column pattern {pattern} matches no column"
+            );
+        }
+    }
+
+    Ok(schema)
+}
+
+fn check_predicate(schema: &Schema, predicate: &Predicate) -> Result<()> {
+    for column in predicate.columns() {
+        ensure!(
+            schema.get(column).is_some(),
+            "cannot find column {column} to filter"
+        );
+    }
+    Ok(())
+}
+
+/// Run the join of a plan with assignment batch by batch, and pass the join result of every
+/// batch to `f`, in order.
+///
+/// The init table is split into batches of `batch_size` rows, each joined lazily with the other
+/// tables. Only the columns the result depends on are read from the tables (see
+/// [`used_columns`]), and the filters that do not read derived columns are pushed down into the
+/// joins. Every join result comes from a single row of the init table, so the batches
+/// partition the join result, except for outer joins, which emit the unmatched rows of the
+/// right table with every batch: plans with an outer join are run in a single batch.
+pub fn join_batches<'a>(
+    df_fn: impl Fn(&str) -> Option<&'a DataFrame>,
+    plan: &JoinPlan,
+    batch_size: usize,
+    mut f: impl FnMut(DataFrame) -> Result<()>,
+) -> Result<()> {
+    ensure!(batch_size > 0, "batch size must be positive");
+    join_schema(&df_fn, plan, true)?;
+
+    let init = df_fn(&plan.init_table).context("cannot find init table")?;
+    let batch_size = if plan.steps.iter().any(|s| s.join_type == JoinType::Outer) {
+        init.height().max(1)
     } else {
-        join_without_assignment(df_fn, plan)
+        batch_size
+    };
+
+    let columns = used_columns(plan);
+    let scan = |df: &DataFrame| {
+        let selected: Vec<Expr> = df
+            .get_column_names()
+            .into_iter()
+            .filter(|c| columns.contains(*c))
+            .map(col)
+            .collect();
+        df.clone().lazy().select(selected)
+    };
+
+    let derived: HashSet<&str> = plan.derived.iter().map(|d| d.name.as_str()).collect();
+    let (late_filters, early_filters): (Vec<Predicate>, Vec<Predicate>) = plan
+        .filters
+        .iter()
+        .cloned()
+        .partition(|p| p.columns().any(|c| derived.contains(c)));
+
+    // an empty init table still has an (empty) batch
+    for offset in (0..init.height().max(1)).step_by(batch_size) {
+        let mut table = scan(&init.slice(offset as i64, batch_size));
+        for step in &plan.steps {
+            let right_table = df_fn(&step.table_to_join).context("cannot find table to join")?;
+            let keys = |keys: &[String]| keys.iter().map(|k| col(k)).collect::<Vec<_>>();
+            table = table
+                .join_builder()
+                .with(scan(right_table))
+                .left_on(keys(&step.left_join_keys))
+                .right_on(keys(&step.right_join_keys))
+                .how(step.join_type.clone())
+                .suffix(format!(":{}", step.table_to_join))
+                .finish();
+
+            let (existing, new): (Vec<_>, Vec<_>) = step
+                .left_join_keys
+                .iter()
+                .zip(step.right_join_keys.iter())
+                .filter(|(l, r)| l != r)
+                .unzip();
+            table = table.rename(existing, new);
+        }
+
+        if init.get_column_names().contains(&ROW_ID_COL_NAME) {
+            table = table.rename(
+                [ROW_ID_COL_NAME],
+                [format!("{}:{}", ROW_ID_COL_NAME, plan.init_table)],
+            );
+        }
+        for predicate in &early_filters {
+            table = table.filter(predicate.to_expr());
+        }
+
+        let mut table = table.collect()?;
+        derive(&mut table, &plan.derived)?;
+        f(filter(table, &late_filters)?)?;
     }
+
+    Ok(())
+}
+
+/// The columns of the source tables that the join result of a plan with assignment depends on:
+/// the row ids, the join keys and the columns read by the derived columns, the filters and the
+/// projection. A column read under its suffixed name `<column>:<table>` is kept in every table,
+/// so that the columns are named as in the full join.
+fn used_columns(plan: &JoinPlan) -> HashSet<String> {
+    let mut read: Vec<&str> = vec![ROW_ID_COL_NAME];
+    for step in &plan.steps {
+        read.extend(step.left_join_keys.iter().map(String::as_str));
+        read.extend(step.right_join_keys.iter().map(String::as_str));
+    }
+    for derived in &plan.derived {
+        read.extend(derived.otherwise.as_column());
+        for case in &derived.cases {
+            read.extend(case.then.as_column());
+            read.extend(case.when.iter().flat_map(Predicate::columns));
+        }
+    }
+    read.extend(plan.filters.iter().flat_map(Predicate::columns));
+    read.extend(plan.projection.iter().map(String::as_str));
+
+    let mut columns = HashSet::new();
+    for column in read {
+        if let Some((base, _)) = column.rsplit_once(':') {
+            columns.insert(base.to_string());
+        }
+        columns.insert(column.to_string());
+    }
+    columns
 }
 
 fn join_with_assignment<'a, 'b>(
-    df_fn: impl Fn(&'a str) -> Option<&'a DataFrame>,
+    df_fn: impl Fn(&str) -> Option<&'a DataFrame>,
     plan: &'b JoinPlan,
 ) -> Result<DataFrame> {
-    let mut table = DataFrame::default();
-    let init_table = df_fn(plan.init_table).context("cannot find init table")?;
+    let mut table = df_fn(&plan.init_table)
+        .context("cannot find init table")?
+        .clone();
 
-    for (i, step) in plan.steps.iter().enumerate() {
-        let left_table = if i == 0 { init_table } else { &table };
-        let right_table = df_fn(step.table_to_join).context("cannot find table to join")?;
-        table = left_table.join(
+    for step in &plan.steps {
+        let right_table = df_fn(&step.table_to_join).context("cannot find table to join")?;
+        table = table.join(
             right_table,
             &step.left_join_keys,
             &step.right_join_keys,
-            step.join_type.to_owned(),
+            step.join_type.clone(),
             Some(format!(":{}", step.table_to_join)),
         )?;
 
@@ -50,16 +328,16 @@ fn join_with_assignment<'a, 'b>(
 }
 
 fn join_without_assignment<'a, 'b>(
-    df_fn: impl Fn(&'a str) -> Option<&'a DataFrame>,
+    df_fn: impl Fn(&str) -> Option<&'a DataFrame>,
     plan: &'b JoinPlan,
 ) -> Result<DataFrame> {
-    let mut table = DataFrame::default();
-    let init_table = df_fn(plan.init_table).context("cannot find init table")?;
+    let mut table = df_fn(&plan.init_table)
+        .context("cannot find init table")?
+        .clone();
 
-    for (i, step) in plan.steps.iter().enumerate() {
-        let left_table = if i == 0 { init_table } else { &table };
-        let right_table = df_fn(step.table_to_join).context("cannot find table to join")?;
-        table = left_table.join(
+    for step in &plan.steps {
+        let right_table = df_fn(&step.table_to_join).context("cannot find table to join")?;
+        table = table.join(
             right_table,
             &step.left_join_keys,
             &step.right_join_keys,
@@ -72,3 +350,86 @@ fn join_without_assignment<'a, 'b>(
 
     Ok(table)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::fixture_dataset, Case, CmpOp, DerivedColumn};
+
+    /// The row ids of every join result, sorted.
+    fn row_ids(df: &DataFrame, plan: &JoinPlan) -> Vec<Vec<Option<u64>>> {
+        let columns: Vec<_> = plan
+            .tables()
+            .map(|t| df.column(&format!("{}:{}", ROW_ID_COL_NAME, t)).unwrap())
+            .collect();
+        let mut rows: Vec<Vec<Option<u64>>> = (0..df.height())
+            .map(|i| columns.iter().map(|c| c.u64().unwrap().get(i)).collect())
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn test_join_batches() {
+        let dataset = fixture_dataset();
+        let df_fn = |table_name: &str| dataset.tables.get(table_name).map(|t| &t.df);
+        let builder = |join_type| {
+            JoinPlan::builder().init_table("customer").join(
+                "orders",
+                ["c_custkey"],
+                ["o_custkey"],
+                join_type,
+            )
+        };
+        let is_open = DerivedColumn {
+            name: "is_open".to_string(),
+            cases: vec![Case {
+                when: vec![Predicate::new(
+                    "o_status",
+                    CmpOp::Eq,
+                    Operand::Str("O".into()),
+                )],
+                then: Operand::Int(1),
+            }],
+            otherwise: Operand::Int(0),
+        };
+        let plans = [
+            builder(JoinType::Inner),
+            builder(JoinType::Left),
+            builder(JoinType::Outer),
+            builder(JoinType::Inner).filter(Predicate::new(
+                "c_name",
+                CmpOp::NotEq,
+                Operand::Str("bob".into()),
+            )),
+            builder(JoinType::Inner)
+                .derive(is_open)
+                .filter(Predicate::new("is_open", CmpOp::Eq, Operand::Int(0)))
+                .filter(Predicate::new("o_orderkey", CmpOp::Gt, Operand::Int(10)))
+                .project(["o_status"]),
+        ];
+
+        for plan in plans {
+            let plan = plan.build_for(&dataset).unwrap();
+            let expected = row_ids(&join(df_fn, &plan, true).unwrap(), &plan);
+            for batch_size in [1, 2, 100] {
+                let mut actual: Option<DataFrame> = None;
+                join_batches(df_fn, &plan, batch_size, |batch| {
+                    // only the row ids and the join key flow through the joins
+                    if plan.filters.is_empty() {
+                        assert_eq!(batch.width(), 3);
+                    }
+                    match &mut actual {
+                        Some(actual) => {
+                            actual.vstack_mut(&batch)?;
+                        }
+                        None => actual = Some(batch),
+                    }
+                    Ok(())
+                })
+                .unwrap();
+                assert_eq!(row_ids(&actual.unwrap(), &plan), expected);
+            }
+        }
+    }
+}
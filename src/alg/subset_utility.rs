@@ -2,7 +2,7 @@ use crate::{Game, OwnerSet};
 use dashmap::DashMap;
 
 pub(crate) fn subset_utility(game: &Game, subset: &OwnerSet) -> f64 {
-    if game.dnf.eval(subset, true) {
+    if game.dnf().eval(subset, true) {
         1.
     } else {
         0.
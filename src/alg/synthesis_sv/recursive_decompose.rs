@@ -1,37 +1,273 @@
 use super::iec::*;
 use crate::{
-    dnf::{recursive_decompose, Dnf, RecursiveDecompose},
+    dnf::{decompose_inner, Decompose, Dnf, RecursiveDecompose, SubExp},
     product_tree::ProductTree,
     utils::hashmap_reduce,
     Game, OwnerId, ShapleyValues,
 };
+use dashmap::DashMap;
 use rayon::prelude::*;
-use std::collections::BTreeSet;
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 pub fn cal_sv_recursive_decompose(game: &Game) -> ShapleyValues {
-    let d = recursive_decompose(&game.dnf, &game.owner_set);
+    cal_sv_decomposed(game.decompose())
+}
+
+/// Compute Shapley values from the decomposition of a game, e.g. one built directly from a
+/// read-once [`BoolExp`](crate::dnf::BoolExp) without expanding it to DNF.
+pub fn cal_sv_decomposed(d: RecursiveDecompose<OwnerId>) -> ShapleyValues {
     let tree = DecomposeTree::new(d, true);
     let gamma_map = IECoeffs::from([(0, 1)]);
     tree.cal_sv(&gamma_map)
 }
 
+/// A dataset-wide memo of decomposition subtrees, shared across rayon workers.
+///
+/// Games sharing a sub-expression, e.g. the same cluster of supplier and part owners in
+/// thousands of output tuples, decompose it and compute its coefficients once: the node of the
+/// sub-expression, with its [`IECoeffs`] and the products of its children, is shared by every
+/// game it occurs in. Sub-expressions are keyed by their minimized DNF, and the nodes of games
+/// built from their decomposition by their children, which are memoized first.
+///
+/// Nothing is evicted, so the memo grows with the number of distinct sub-expressions of the
+/// games. The roots of the games are rarely shared and are not kept, so that computing games
+/// chunk by chunk keeps a memory bounded by the sub-expressions rather than by the games.
+#[derive(Default)]
+pub struct DecomposeMemo {
+    trees: DashMap<MemoKey, Arc<DecomposeTree>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum MemoKey {
+    /// A sub-expression decomposed from its DNF, over the given variables, and whether
+    /// connected components are tried first.
+    Exp(Dnf<OwnerId>, bool, BTreeSet<OwnerId>),
+    /// A node of a decomposition, by its children (see [`NodeId`]), sorted unless the order
+    /// matters.
+    And(Vec<NodeId>),
+    Or(Vec<NodeId>),
+    Hybrid(Dnf<usize>, Vec<NodeId>),
+}
+
+/// The identity of a child node: a variable, or the address of a memoized node. Memoized
+/// nodes are kept alive by the memo along with the nodes whose keys refer to them, so that an
+/// address is never reused while it is in a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum NodeId {
+    Var(OwnerId),
+    Node(usize),
+}
+
+impl NodeId {
+    fn of(tree: &Arc<DecomposeTree>) -> Self {
+        match tree.as_ref() {
+            DecomposeTree::Var(var) => Self::Var(*var),
+            _ => Self::Node(Arc::as_ptr(tree) as usize),
+        }
+    }
+}
+
+impl DecomposeMemo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compute Shapley values as [`cal_sv_recursive_decompose`] does, looking the
+    /// sub-expressions of the game up in the memo. A game built from its decomposition is not
+    /// decomposed again, but its nodes are looked up as well. The root of the game is built
+    /// without looking it up, and is not kept.
+    pub fn cal_sv(&self, game: &Game) -> ShapleyValues {
+        let tree = match (game.factored(), game.owner_set.iter().next()) {
+            (Some(d), _) => self.factored_root(d),
+            (None, Some(&owner_id)) if game.owner_len() == 1 => DecomposeTree::Var(owner_id),
+            _ => self.build(game.dnf(), &game.owner_set, true, true),
+        };
+        let gamma_map = IECoeffs::from([(0, 1)]);
+        tree.cal_sv(&gamma_map)
+    }
+
+    /// The number of sub-expressions found in the memo.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of sub-expressions decomposed.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// The number of sub-expressions in the memo.
+    pub fn len(&self) -> usize {
+        self.trees.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
+    }
+
+    /// The fraction of sub-expressions found in the memo.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits();
+        let total = hits + self.misses();
+        if total == 0 {
+            0.
+        } else {
+            hits as f64 / total as f64
+        }
+    }
+
+    /// The memoized tree of `key`, built by `build` unless it is in the memo. Of the trees of
+    /// the same key built concurrently, the first one inserted is kept.
+    fn get_or_build(
+        &self,
+        key: MemoKey,
+        build: impl FnOnce() -> DecomposeTree,
+    ) -> Arc<DecomposeTree> {
+        if let Some(tree) = self.trees.get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return tree.clone();
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // the memo is not locked while building, as building looks the children up
+        let tree = Arc::new(build());
+        self.trees.entry(key).or_insert(tree).clone()
+    }
+
+    /// The memoized tree of `exp`, with the coefficients of its root.
+    fn tree(
+        &self,
+        exp: &Dnf<OwnerId>,
+        all_variables: &BTreeSet<OwnerId>,
+        try_cc: bool,
+    ) -> Arc<DecomposeTree> {
+        let key = MemoKey::Exp(exp.clone(), try_cc, all_variables.clone());
+        self.get_or_build(key, || self.build(exp, all_variables, try_cc, false))
+    }
+
+    /// The tree of `exp` as [`recursive_decompose`](crate::dnf::recursive_decompose) decomposes
+    /// it, with its sub-expressions looked up in the memo.
+    fn build(
+        &self,
+        exp: &Dnf<OwnerId>,
+        all_variables: &BTreeSet<OwnerId>,
+        try_cc: bool,
+        is_root: bool,
+    ) -> DecomposeTree {
+        let (d, modular_set_list) = decompose_inner(exp, all_variables, try_cc);
+        match d {
+            Decompose::Var(var) => DecomposeTree::Var(var),
+            Decompose::And(list) => {
+                DecomposeTree::and(self.children(list, modular_set_list, true), is_root)
+            }
+            Decompose::Or(list) => {
+                DecomposeTree::or(self.children(list, modular_set_list, false), is_root)
+            }
+            Decompose::Hybrid {
+                hybrid_exp,
+                sub_exps,
+            } => DecomposeTree::hybrid(
+                hybrid_exp,
+                self.children(sub_exps, modular_set_list, true),
+                is_root,
+            ),
+        }
+    }
+
+    /// The tree of the root of a decomposition, whose children are looked up in the memo.
+    fn factored_root(&self, d: &RecursiveDecompose<OwnerId>) -> DecomposeTree {
+        match d {
+            RecursiveDecompose::Var(var) => DecomposeTree::Var(*var),
+            RecursiveDecompose::And(list) => DecomposeTree::and(self.factored_children(list), true),
+            RecursiveDecompose::Or(list) => DecomposeTree::or(self.factored_children(list), true),
+            RecursiveDecompose::Hybrid {
+                hybrid_exp,
+                sub_exps,
+            } => DecomposeTree::hybrid(hybrid_exp.clone(), self.factored_children(sub_exps), true),
+        }
+    }
+
+    /// The tree of a decomposition, with the coefficients of its root. Its nodes are
+    /// hash-consed: the children of a node are looked up first, and the node by their identity.
+    fn factored_tree(&self, d: &RecursiveDecompose<OwnerId>) -> Arc<DecomposeTree> {
+        let sorted_ids = |children: &[Arc<DecomposeTree>]| {
+            let mut ids: Vec<NodeId> = children.iter().map(NodeId::of).collect();
+            ids.sort_unstable();
+            ids
+        };
+        match d {
+            RecursiveDecompose::Var(var) => Arc::new(DecomposeTree::Var(*var)),
+            RecursiveDecompose::And(list) => {
+                let children = self.factored_children(list);
+                self.get_or_build(MemoKey::And(sorted_ids(&children)), || {
+                    DecomposeTree::and(children, false)
+                })
+            }
+            RecursiveDecompose::Or(list) => {
+                let children = self.factored_children(list);
+                self.get_or_build(MemoKey::Or(sorted_ids(&children)), || {
+                    DecomposeTree::or(children, false)
+                })
+            }
+            RecursiveDecompose::Hybrid {
+                hybrid_exp,
+                sub_exps,
+            } => {
+                let children = self.factored_children(sub_exps);
+                // the hybrid expression refers to the children by position
+                let ids = children.iter().map(NodeId::of).collect();
+                self.get_or_build(MemoKey::Hybrid(hybrid_exp.clone(), ids), || {
+                    DecomposeTree::hybrid(hybrid_exp.clone(), children, false)
+                })
+            }
+        }
+    }
+
+    fn factored_children(&self, list: &[RecursiveDecompose<OwnerId>]) -> Vec<Arc<DecomposeTree>> {
+        list.par_iter().map(|d| self.factored_tree(d)).collect()
+    }
+
+    fn children(
+        &self,
+        list: Vec<SubExp<OwnerId>>,
+        modular_set_list: Vec<BTreeSet<OwnerId>>,
+        try_cc_in_recursive: bool,
+    ) -> Vec<Arc<DecomposeTree>> {
+        list.into_par_iter()
+            .enumerate()
+            .map(|(i, sub_exp)| match sub_exp {
+                SubExp::Exp(sub) => self.tree(&sub, &modular_set_list[i], try_cc_in_recursive),
+                SubExp::Var(var) => Arc::new(DecomposeTree::Var(var)),
+            })
+            .collect()
+    }
+}
+
 enum DecomposeTree {
     Var(OwnerId),
     And {
         coeffs: Option<IECoeffs>,
         products: Vec<IECoeffs>,
-        children: Vec<DecomposeTree>,
+        children: Vec<Arc<DecomposeTree>>,
     },
     Or {
         coeffs: Option<IECoeffs>,
         products: Vec<IECoeffs>,
-        children: Vec<DecomposeTree>,
+        children: Vec<Arc<DecomposeTree>>,
     },
     Hybrid {
         coeffs: Option<IECoeffs>,
         hybrid_coeffs: HybridCoeffs,
         hybrid_exp: Dnf<usize>,
-        children: Vec<DecomposeTree>,
+        children: Vec<Arc<DecomposeTree>>,
     },
 }
 
@@ -39,77 +275,79 @@ impl DecomposeTree {
     fn new(input: RecursiveDecompose<OwnerId>, is_root: bool) -> Self {
         match input {
             RecursiveDecompose::Var(id) => Self::Var(id),
-            RecursiveDecompose::And(children) => {
-                let children: Vec<_> = children
-                    .into_par_iter()
-                    .map(|c| DecomposeTree::new(c, false))
-                    .collect();
-                let mut children_coeffs = Vec::with_capacity(children.len());
-                for c in &children {
-                    children_coeffs.push(c.coeffs());
-                }
-                let product_tree: ProductTree<IECoeffs> =
-                    ProductTree::new(children_coeffs, vertical_op, !is_root);
-                let products = product_tree.all_products(vertical_identity, vertical_op);
-                let coeffs = if is_root {
-                    None
-                } else {
-                    Some(product_tree.root())
-                };
-                Self::And {
-                    coeffs,
-                    products,
-                    children,
-                }
-            }
-            RecursiveDecompose::Or(children) => {
-                let children: Vec<_> = children
-                    .into_par_iter()
-                    .map(|c| DecomposeTree::new(c, false))
-                    .collect();
-                let mut children_coeffs = Vec::with_capacity(children.len());
-                for c in &children {
-                    children_coeffs.push(c.coeffs());
-                }
-                let product_tree: ProductTree<IECoeffs> =
-                    ProductTree::new(children_coeffs, horizontal_op, !is_root);
-                let products = product_tree.all_products(horizontal_identity, horizontal_op);
-                let coeffs = if is_root {
-                    None
-                } else {
-                    Some(product_tree.root())
-                };
-                Self::Or {
-                    coeffs,
-                    products,
-                    children,
-                }
-            }
+            RecursiveDecompose::And(children) => Self::and(Self::new_children(children), is_root),
+            RecursiveDecompose::Or(children) => Self::or(Self::new_children(children), is_root),
             RecursiveDecompose::Hybrid {
                 hybrid_exp,
                 sub_exps,
-            } => {
-                let children: Vec<_> = sub_exps
-                    .into_par_iter()
-                    .map(|c| DecomposeTree::new(c, false))
-                    .collect();
-                let mut children_coeffs = Vec::with_capacity(children.len());
-                for c in &children {
-                    children_coeffs.push(c.coeffs());
-                }
-                let hybrid_coeffs = HybridCoeffs::new(&children_coeffs);
-                let coeffs = if is_root {
-                    None
-                } else {
-                    Some(hybrid_coeffs.exp_coeffs(&hybrid_exp))
-                };
-                Self::Hybrid {
-                    coeffs,
-                    hybrid_coeffs,
-                    hybrid_exp,
-                    children,
-                }
-            }
+            } => Self::hybrid(hybrid_exp, Self::new_children(sub_exps), is_root),
+        }
+    }
+
+    fn new_children(children: Vec<RecursiveDecompose<OwnerId>>) -> Vec<Arc<Self>> {
+        children
+            .into_par_iter()
+            .map(|c| Arc::new(DecomposeTree::new(c, false)))
+            .collect()
+    }
+
+    /// The coefficients of the root are not needed, so they are not computed.
+    fn and(children: Vec<Arc<Self>>, is_root: bool) -> Self {
+        let mut children_coeffs = Vec::with_capacity(children.len());
+        for c in &children {
+            children_coeffs.push(c.coeffs());
+        }
+        let product_tree: ProductTree<IECoeffs> =
+            ProductTree::new(children_coeffs, vertical_op, !is_root);
+        let products = product_tree.all_products(vertical_identity, vertical_op);
+        let coeffs = if is_root {
+            None
+        } else {
+            Some(product_tree.root())
+        };
+        Self::And {
+            coeffs,
+            products,
+            children,
+        }
+    }
+
+    fn or(children: Vec<Arc<Self>>, is_root: bool) -> Self {
+        let mut children_coeffs = Vec::with_capacity(children.len());
+        for c in &children {
+            children_coeffs.push(c.coeffs());
+        }
+        let product_tree: ProductTree<IECoeffs> =
+            ProductTree::new(children_coeffs, horizontal_op, !is_root);
+        let products = product_tree.all_products(horizontal_identity, horizontal_op);
+        let coeffs = if is_root {
+            None
+        } else {
+            Some(product_tree.root())
+        };
+        Self::Or {
+            coeffs,
+            products,
+            children,
+        }
+    }
+
+    fn hybrid(hybrid_exp: Dnf<usize>, children: Vec<Arc<Self>>, is_root: bool) -> Self {
+        let mut children_coeffs = Vec::with_capacity(children.len());
+        for c in &children {
+            children_coeffs.push(c.coeffs());
+        }
+        let hybrid_coeffs = HybridCoeffs::new(&children_coeffs);
+        let coeffs = if is_root {
+            None
+        } else {
+            Some(hybrid_coeffs.exp_coeffs(&hybrid_exp))
+        };
+        Self::Hybrid {
+            coeffs,
+            hybrid_coeffs,
+            hybrid_exp,
+            children,
         }
     }
 
@@ -135,7 +373,7 @@ impl DecomposeTree {
                 let var_children: Vec<_> = children
                     .iter()
                     .enumerate()
-                    .filter_map(|(i, c)| match c {
+                    .filter_map(|(i, c)| match c.as_ref() {
                         Self::Var(id) => Some((i, id)),
                         _ => None,
                     })
@@ -144,7 +382,7 @@ impl DecomposeTree {
                 let mut ans = children
                     .par_iter()
                     .enumerate()
-                    .filter(|(_, c)| !matches!(c, Self::Var(_)))
+                    .filter(|(_, c)| !matches!(c.as_ref(), Self::Var(_)))
                     .map(|(i, c)| {
                         let iece_map = &products[i];
                         let next_gamma_map = gamma_map * iece_map;
@@ -169,7 +407,7 @@ impl DecomposeTree {
                 let var_children: Vec<_> = children
                     .iter()
                     .enumerate()
-                    .filter_map(|(i, c)| match c {
+                    .filter_map(|(i, c)| match c.as_ref() {
                         Self::Var(id) => Some((i, id)),
                         _ => None,
                     })
@@ -178,7 +416,7 @@ impl DecomposeTree {
                 let mut ans = children
                     .par_iter()
                     .enumerate()
-                    .filter(|(_, c)| !matches!(c, Self::Var(_)))
+                    .filter(|(_, c)| !matches!(c.as_ref(), Self::Var(_)))
                     .map(|(i, c)| {
                         let iece_map = &products[i];
                         let next_gamma_map = gamma_map - &(gamma_map * iece_map);
@@ -225,25 +463,32 @@ impl DecomposeTree {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{dnf, tests::assert_f64_eq, OwnerSet};
+    use crate::{dnf, dnf::BoolExp, tests::assert_f64_eq, tests::fixture_dataset, JoinPlan, RowId};
+    use polars::prelude::JoinType;
+
+    #[test]
+    fn test_cal_sv_decomposed() {
+        let exp: BoolExp<OwnerId> =
+            r#"{"and": [1, {"or": [2, {"and": [3, 4]}]}, 5]}"#.parse().unwrap();
+        let sv = cal_sv_decomposed(exp.to_decompose().unwrap());
+        let expect = cal_sv_recursive_decompose(&Game::new(exp.to_dnf()));
+        assert_eq!(sv.len(), expect.len());
+        for (owner, value) in expect {
+            assert_f64_eq(value, sv[&owner]);
+        }
+    }
 
     #[test]
     fn test_cal_sv_recursive_decompose() {
         // test for complementary owners
-        let game = Game {
-            dnf: dnf!(1 2 3).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3)]),
-        };
+        let game = Game::new(dnf!(1 2 3).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose(&game);
         assert_f64_eq(0.33333333333, sv[&OwnerId(1)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(2)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(3)]);
 
-        let game = Game {
-            dnf: dnf!(1 2 3 + 1 2 4 ).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3), OwnerId(4)]),
-        };
+        let game = Game::new(dnf!(1 2 3 + 1 2 4 ).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose(&game);
         assert_f64_eq(0.41666666666, sv[&OwnerId(1)]);
@@ -251,10 +496,7 @@ mod tests {
         assert_f64_eq(0.08333333333, sv[&OwnerId(3)]);
         assert_f64_eq(0.08333333333, sv[&OwnerId(4)]);
 
-        let game = Game {
-            dnf: dnf!(1 2 3 4 + 1 2 3 5 + 6).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3), OwnerId(4)]),
-        };
+        let game = Game::new(dnf!(1 2 3 4 + 1 2 3 5 + 6).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose(&game);
         assert_f64_eq(0.06666666666, sv[&OwnerId(1)]);
@@ -265,26 +507,14 @@ mod tests {
         assert_f64_eq(0.76666666666, sv[&OwnerId(6)]);
 
         // test for replaceable owners
-        let game = Game {
-            dnf: dnf!(1 + 2 + 3).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3)]),
-        };
+        let game = Game::new(dnf!(1 + 2 + 3).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose(&game);
         assert_f64_eq(0.33333333333, sv[&OwnerId(1)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(2)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(3)]);
 
-        let game = Game {
-            dnf: dnf!(1 4 5 + 2 4 5 + 3 4 5).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(2),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-            ]),
-        };
+        let game = Game::new(dnf!(1 4 5 + 2 4 5 + 3 4 5).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose(&game);
         assert_f64_eq(0.03333333333, sv[&OwnerId(1)]);
@@ -294,16 +524,8 @@ mod tests {
         assert_f64_eq(0.45, sv[&OwnerId(5)]);
 
         // test for hybrid
-        let game = Game {
-            dnf: dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(2),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-            ]),
-        };
+        let game =
+            Game::new(dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose(&game);
         assert_f64_eq(0.06666666666, sv[&OwnerId(1)]);
@@ -313,18 +535,7 @@ mod tests {
         assert_f64_eq(0.31666666666, sv[&OwnerId(5)]);
 
         // test recursive
-        let game = Game {
-            dnf: dnf!(1 3 6 8 + 3 5 6 8 + 3 4 6 8 9).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-                OwnerId(6),
-                OwnerId(8),
-                OwnerId(9),
-            ]),
-        };
+        let game = Game::new(dnf!(1 3 6 8 + 3 5 6 8 + 3 4 6 8 9).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose(&game);
         assert_f64_eq(0.3095238095238095, sv[&OwnerId(3)]);
@@ -336,24 +547,83 @@ mod tests {
         assert_f64_eq(0.009523809523809545, sv[&OwnerId(9)]);
     }
 
+    #[test]
+    fn test_decompose_memo() {
+        // the games share the sub-expressions `1 2 + 3` and `4 5 + 6 7`
+        let games = [
+            "1 2 8 + 3 8",
+            "1 2 9 + 3 9 + 4 5 + 6 7",
+            "1 2 4 5 + 1 2 6 7 + 3 4 5 + 3 6 7",
+            "1 2 4 5 + 1 2 6 7 + 3 4 5 + 3 6 7",
+            "1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5",
+            "10",
+        ];
+
+        let memo = DecomposeMemo::new();
+        for game in games {
+            let game = Game::new(game.parse().unwrap());
+            let expect = cal_sv_recursive_decompose(&game);
+            let sv = memo.cal_sv(&game);
+            assert_eq!(sv.len(), expect.len());
+            for (owner, value) in expect {
+                assert_f64_eq(value, sv[&owner]);
+            }
+        }
+        assert!(memo.hits() >= 3);
+        assert_eq!(memo.len(), memo.misses());
+
+        // games built from their decomposition are memoized node by node, below their root
+        let exp: BoolExp<OwnerId> = r#"{"and": [1, {"or": [2, 3]}]}"#.parse().unwrap();
+        let game = Game::from_decompose(exp.to_decompose().unwrap());
+        let expect = cal_sv_recursive_decompose(&game);
+        let misses = memo.misses();
+        let sv = memo.cal_sv(&game);
+        for (owner, value) in expect {
+            assert_f64_eq(value, sv[&owner]);
+        }
+        assert_eq!(memo.misses(), misses + 1);
+        let (hits, len) = (memo.hits(), memo.len());
+        memo.cal_sv(&game);
+        assert_eq!(
+            (memo.hits(), memo.misses(), memo.len()),
+            (hits + 1, misses + 1, len)
+        );
+    }
+
+    #[test]
+    fn test_decompose_memo_join() {
+        // both orders of alice join her row with two owners
+        let mut dataset = fixture_dataset();
+        let customer = dataset.tables.get_mut("customer").unwrap();
+        customer.owner_map = [(0, 1), (0, 8), (1, 2), (2, 6)]
+            .into_iter()
+            .map(|(row_id, owner)| (RowId(row_id), OwnerId(owner)))
+            .collect();
+        let plan = JoinPlan::builder()
+            .init_table("customer")
+            .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Inner)
+            .build_for(&dataset)
+            .unwrap();
+        let games = Game::generate_games(&dataset, &plan).unwrap();
+        assert!(games.iter().all(|game| game.factored().is_some()));
+
+        let memo = DecomposeMemo::new();
+        for game in &games {
+            let expect = cal_sv_recursive_decompose(game);
+            let sv = memo.cal_sv(game);
+            assert_eq!(sv.len(), expect.len());
+            for (owner, value) in expect {
+                assert_f64_eq(value, sv[&owner]);
+            }
+        }
+        // the OR of alice's owners is shared by her games
+        assert_eq!(memo.hits(), 1);
+        assert!(memo.len() < games.len() * 2);
+    }
+
     #[test]
     fn test_performance() {
-        let game = Game {
-                dnf: dnf!(0 4 12 17 + 0 7 12 17 + 0 4 5 9 17 + 0 4 5 10 17 + 0 4 9 15 17 + 0 4 10 15 17 + 4 5 10 13 17 + 4 10 12 13 17 + 4 10 13 15 17 + 7 10 12 13 17 + 0 5 6 7 9 17 + 0 5 6 7 10 17 + 0 6 7 9 15 17 + 0 6 7 10 15 17 + 5 6 7 10 13 17 + 6 7 10 13 15 17).map_variable(|id| OwnerId(*id)),
-                owner_set: OwnerSet::from_iter([
-                    OwnerId(0),
-                    OwnerId(4),
-                    OwnerId(5),
-                    OwnerId(6),
-                    OwnerId(7),
-                    OwnerId(9),
-                    OwnerId(10),
-                    OwnerId(12),
-                    OwnerId(13),
-                    OwnerId(15),
-                    OwnerId(17),
-                ]),
-            };
+        let game = Game::new(dnf!(0 4 12 17 + 0 7 12 17 + 0 4 5 9 17 + 0 4 5 10 17 + 0 4 9 15 17 + 0 4 10 15 17 + 4 5 10 13 17 + 4 10 12 13 17 + 4 10 13 15 17 + 7 10 12 13 17 + 0 5 6 7 9 17 + 0 5 6 7 10 17 + 0 6 7 9 15 17 + 0 6 7 10 15 17 + 5 6 7 10 13 17 + 6 7 10 13 15 17).map_variable(|id| OwnerId(*id)));
 
         let _ = cal_sv_recursive_decompose(&game);
     }
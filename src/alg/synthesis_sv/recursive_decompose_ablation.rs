@@ -21,7 +21,7 @@ pub fn cal_sv_recursive_decompose_ablation(
     game: &Game,
     ablation_type: AblationType,
 ) -> ShapleyValues {
-    let d = recursive_decompose(&game.dnf, &game.owner_set);
+    let d = recursive_decompose(game.dnf(), &game.owner_set);
     let tree = DecomposeTree::new(d, true, ablation_type);
     let gamma_map = IECoeffs::from([(0, 1)]);
     tree.cal_sv(&gamma_map)
@@ -345,25 +345,19 @@ fn leaf_exp_unions_interaction(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{dnf, tests::assert_f64_eq, OwnerSet};
+    use crate::{dnf, tests::assert_f64_eq};
 
     #[test]
     fn test_cal_sv_recursive_decompose_ablation() {
         // test for complementary owners
-        let game = Game {
-            dnf: dnf!(1 2 3).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3)]),
-        };
+        let game = Game::new(dnf!(1 2 3).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.33333333333, sv[&OwnerId(1)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(2)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(3)]);
 
-        let game = Game {
-            dnf: dnf!(1 2 3 + 1 2 4 ).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3), OwnerId(4)]),
-        };
+        let game = Game::new(dnf!(1 2 3 + 1 2 4 ).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.41666666666, sv[&OwnerId(1)]);
@@ -371,10 +365,7 @@ mod tests {
         assert_f64_eq(0.08333333333, sv[&OwnerId(3)]);
         assert_f64_eq(0.08333333333, sv[&OwnerId(4)]);
 
-        let game = Game {
-            dnf: dnf!(1 2 3 4 + 1 2 3 5 + 6).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3), OwnerId(4)]),
-        };
+        let game = Game::new(dnf!(1 2 3 4 + 1 2 3 5 + 6).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.06666666666, sv[&OwnerId(1)]);
@@ -385,26 +376,14 @@ mod tests {
         assert_f64_eq(0.76666666666, sv[&OwnerId(6)]);
 
         // test for replaceable owners
-        let game = Game {
-            dnf: dnf!(1 + 2 + 3).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3)]),
-        };
+        let game = Game::new(dnf!(1 + 2 + 3).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.33333333333, sv[&OwnerId(1)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(2)]);
         assert_f64_eq(0.33333333333, sv[&OwnerId(3)]);
 
-        let game = Game {
-            dnf: dnf!(1 4 5 + 2 4 5 + 3 4 5).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(2),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-            ]),
-        };
+        let game = Game::new(dnf!(1 4 5 + 2 4 5 + 3 4 5).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.03333333333, sv[&OwnerId(1)]);
@@ -414,16 +393,8 @@ mod tests {
         assert_f64_eq(0.45, sv[&OwnerId(5)]);
 
         // test for hybrid
-        let game = Game {
-            dnf: dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(2),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-            ]),
-        };
+        let game =
+            Game::new(dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.06666666666, sv[&OwnerId(1)]);
@@ -433,18 +404,7 @@ mod tests {
         assert_f64_eq(0.31666666666, sv[&OwnerId(5)]);
 
         // test recursive
-        let game = Game {
-            dnf: dnf!(1 3 6 8 + 3 5 6 8 + 3 4 6 8 9).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-                OwnerId(6),
-                OwnerId(8),
-                OwnerId(9),
-            ]),
-        };
+        let game = Game::new(dnf!(1 3 6 8 + 3 5 6 8 + 3 4 6 8 9).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.3095238095238095, sv[&OwnerId(3)]);
@@ -458,22 +418,7 @@ mod tests {
 
     #[test]
     fn test_performance() {
-        let game = Game {
-                dnf: dnf!(0 4 12 17 + 0 7 12 17 + 0 4 5 9 17 + 0 4 5 10 17 + 0 4 9 15 17 + 0 4 10 15 17 + 4 5 10 13 17 + 4 10 12 13 17 + 4 10 13 15 17 + 7 10 12 13 17 + 0 5 6 7 9 17 + 0 5 6 7 10 17 + 0 6 7 9 15 17 + 0 6 7 10 15 17 + 5 6 7 10 13 17 + 6 7 10 13 15 17).map_variable(|id| OwnerId(*id)),
-                owner_set: OwnerSet::from_iter([
-                    OwnerId(0),
-                    OwnerId(4),
-                    OwnerId(5),
-                    OwnerId(6),
-                    OwnerId(7),
-                    OwnerId(9),
-                    OwnerId(10),
-                    OwnerId(12),
-                    OwnerId(13),
-                    OwnerId(15),
-                    OwnerId(17),
-                ]),
-            };
+        let game = Game::new(dnf!(0 4 12 17 + 0 7 12 17 + 0 4 5 9 17 + 0 4 5 10 17 + 0 4 9 15 17 + 0 4 10 15 17 + 4 5 10 13 17 + 4 10 12 13 17 + 4 10 13 15 17 + 7 10 12 13 17 + 0 5 6 7 9 17 + 0 5 6 7 10 17 + 0 6 7 9 15 17 + 0 6 7 10 15 17 + 5 6 7 10 13 17 + 6 7 10 13 15 17).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoHybrid);
         assert_f64_eq(0.013492063492063444, sv[&OwnerId(6)]);
@@ -487,16 +432,8 @@ mod tests {
 
     #[test]
     fn test_ablation() {
-        let game = Game {
-            dnf: dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(2),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-            ]),
-        };
+        let game =
+            Game::new(dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.06666666666, sv[&OwnerId(1)]);
@@ -522,17 +459,9 @@ mod tests {
 
     #[test]
     fn test_vertical_decom() {
-        let game = Game {
-            dnf: dnf!(1 2 5 + 1 2 6 + 1 3 5 + 1 3 6 + 4 5 + 4 6).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([
-                OwnerId(1),
-                OwnerId(2),
-                OwnerId(3),
-                OwnerId(4),
-                OwnerId(5),
-                OwnerId(6),
-            ]),
-        };
+        let game = Game::new(
+            dnf!(1 2 5 + 1 2 6 + 1 3 5 + 1 3 6 + 4 5 + 4 6).map_variable(|id| OwnerId(*id)),
+        );
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoVertical);
         assert_f64_eq(0.16666666666, sv[&OwnerId(1)]);
@@ -546,10 +475,7 @@ mod tests {
 
     #[test]
     fn test_horizontal_decom() {
-        let game = Game {
-            dnf: dnf!(1 2 + 1 3 + 4 ).map_variable(|id| OwnerId(*id)),
-            owner_set: OwnerSet::from_iter([OwnerId(1), OwnerId(2), OwnerId(3), OwnerId(4)]),
-        };
+        let game = Game::new(dnf!(1 2 + 1 3 + 4 ).map_variable(|id| OwnerId(*id)));
 
         let sv = cal_sv_recursive_decompose_ablation(&game, AblationType::NoHorizontal);
         assert_f64_eq(0.25, sv[&OwnerId(1)]);
@@ -0,0 +1,339 @@
+//! Synthetic assignment of table rows to data owners, ported from `scripts/assign_data.py`.
+//!
+//! Every table gets a set of owners. Its records are split into groups with 1, 2, ..., up to
+//! `max_copy` copies by a Zipf distribution of exponent `alpha`, and each copy of a record is
+//! given to a distinct owner of the table, picked uniformly (`equal_records`) or by a Zipf
+//! distribution of exponent `beta` over the owners. Afterwards, every owner without record
+//! takes over a copy from an owner with more than one.
+
+use crate::{OwnerId, RowId};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use polars::prelude::*;
+use rand::{prelude::*, seq::index};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use zipf::ZipfDistribution;
+
+/// Tables owned by `owners_for_small_table` owners only.
+pub const SMALL_TABLES: &[&str] = &["region", "nation"];
+
+/// The table owned by all owners when owners are not assigned equally.
+pub const LARGEST_TABLE: &str = "lineitem";
+
+#[derive(Debug, Clone)]
+pub struct AssignConfig {
+    /// Zipf exponent of the number of records per number of copies.
+    pub alpha: f64,
+    /// Zipf exponent of the probability of an owner to get a copy of a record.
+    pub beta: f64,
+    pub num_owners: usize,
+    pub max_copy: usize,
+    /// Give every table (except the small ones) all owners, rather than `basic_owners` owners.
+    pub equal_owners: bool,
+    /// Pick the owners of a record uniformly rather than by a Zipf distribution.
+    pub equal_records: bool,
+    pub owners_for_small_table: usize,
+    pub basic_owners: usize,
+}
+
+impl Default for AssignConfig {
+    fn default() -> Self {
+        Self {
+            alpha: 3.0,
+            beta: 3.0,
+            num_owners: 500,
+            max_copy: 4,
+            equal_owners: true,
+            equal_records: true,
+            owners_for_small_table: 5,
+            basic_owners: 10,
+        }
+    }
+}
+
+/// Sample the owners of every table among `0..num_owners`.
+pub fn assign_owners_to_tables(
+    rng: &mut impl Rng,
+    tables: &[&str],
+    config: &AssignConfig,
+) -> Vec<Vec<OwnerId>> {
+    tables
+        .iter()
+        .map(|&table| {
+            let count = if SMALL_TABLES.contains(&table) {
+                config.owners_for_small_table
+            } else if config.equal_owners || table == LARGEST_TABLE {
+                config.num_owners
+            } else {
+                config.basic_owners
+            };
+            index::sample(rng, config.num_owners, count.min(config.num_owners))
+                .into_iter()
+                .map(|i| OwnerId(i as u32))
+                .collect()
+        })
+        .collect()
+}
+
+/// Assign every record `0..num_records` of a table to some of `owners`, returning the
+/// `(record, owner)` pairs.
+pub fn assign_records_to_owners(
+    rng: &mut impl Rng,
+    num_records: usize,
+    owners: &[OwnerId],
+    config: &AssignConfig,
+) -> Result<Vec<(RowId, OwnerId)>> {
+    ensure!(!owners.is_empty(), "no owner to assign records to");
+    let owner_dist = ZipfDistribution::new(owners.len(), config.beta)
+        .map_err(|_| anyhow!("invalid beta {}", config.beta))?;
+
+    let counts = records_per_copy(config.alpha, owners.len().min(config.max_copy), num_records)?;
+    let mut pairs = vec![];
+    let mut record = 0;
+    for (i, count) in counts.into_iter().enumerate() {
+        let copies = i + 1;
+        for _ in 0..count {
+            let record_owners: Vec<OwnerId> = if config.equal_records {
+                index::sample(rng, owners.len(), copies)
+                    .into_iter()
+                    .map(|j| owners[j])
+                    .collect()
+            } else {
+                // weighted sampling without replacement
+                let mut picked = BTreeSet::new();
+                let mut ans = vec![];
+                while ans.len() < copies {
+                    let j = owner_dist.sample(rng) - 1;
+                    if picked.insert(j) {
+                        ans.push(owners[j]);
+                    }
+                }
+                ans
+            };
+            pairs.extend(record_owners.into_iter().map(|o| (RowId(record), o)));
+            record += 1;
+        }
+    }
+
+    assign_owners_without_records(owners, &mut pairs);
+    Ok(pairs)
+}
+
+/// Split `num_records` records by their number of copies `1..=max_copy`, following a Zipf
+/// distribution. The records lost by rounding have a single copy.
+fn records_per_copy(alpha: f64, max_copy: usize, num_records: usize) -> Result<Vec<usize>> {
+    ensure!(alpha > 0., "invalid alpha {alpha}");
+    let weights: Vec<f64> = (1..=max_copy)
+        .map(|i| 1. / (i as f64).powf(alpha))
+        .collect();
+    let total: f64 = weights.iter().sum();
+    let mut counts: Vec<usize> = weights
+        .iter()
+        .map(|w| (w / total * num_records as f64) as usize)
+        .collect();
+    let rest = num_records - counts.iter().sum::<usize>();
+    if let Some(first) = counts.first_mut() {
+        *first += rest;
+    }
+    Ok(counts)
+}
+
+/// Give every owner without record a copy taken from an owner with more than one.
+fn assign_owners_without_records(owners: &[OwnerId], pairs: &mut [(RowId, OwnerId)]) {
+    let mut counts = HashMap::new();
+    for (_, owner) in pairs.iter() {
+        *counts.entry(*owner).or_insert(0usize) += 1;
+    }
+
+    let missing: Vec<OwnerId> = owners
+        .iter()
+        .filter(|o| !counts.contains_key(o))
+        .copied()
+        .collect();
+    let mut i = 0;
+    for owner in missing {
+        while i < pairs.len() && counts[&pairs[i].1] < 2 {
+            i += 1;
+        }
+        if i == pairs.len() {
+            break;
+        }
+        *counts.get_mut(&pairs[i].1).unwrap() -= 1;
+        counts.insert(owner, 1);
+        pairs[i].1 = owner;
+        i += 1;
+    }
+}
+
+/// Shuffled row ids of the `num_records` rows of a source file: row `i` gets row id `ans[i]`.
+pub fn shuffle_row_ids(rng: &mut impl Rng, num_records: usize) -> Vec<u64> {
+    let mut row_ids: Vec<u64> = (0..num_records as u64).collect();
+    row_ids.shuffle(rng);
+    row_ids
+}
+
+/// Write the assignment of table `name` to `dir` in the format of `scripts/assign_data.py`:
+/// `<name>-index.json` with the row id of every source row, and `<name>-owner.json` with the
+/// `(row id, owner)` pairs as pandas columns.
+pub fn write_json(
+    dir: &Path,
+    name: &str,
+    row_ids: &[u64],
+    pairs: &[(RowId, OwnerId)],
+) -> Result<()> {
+    let index_f = dir.join(format!("{name}-index.json"));
+    let out = BufWriter::new(File::create(&index_f)?);
+    serde_json::to_writer(out, row_ids)
+        .with_context(|| format!("failed to write {}", index_f.display()))?;
+
+    let owner_f = dir.join(format!("{name}-owner.json"));
+    let mut out = BufWriter::new(File::create(&owner_f)?);
+    write_pandas_columns(&mut out, pairs)
+        .with_context(|| format!("failed to write {}", owner_f.display()))
+}
+
+/// Write `(row id, owner)` pairs as the JSON of a pandas data frame with `index` and `owner`
+/// columns, keyed by the stringified positions of the pairs.
+fn write_pandas_columns(out: &mut impl Write, pairs: &[(RowId, OwnerId)]) -> io::Result<()> {
+    fn write_column(
+        out: &mut impl Write,
+        column: &str,
+        values: impl Iterator<Item = impl fmt::Display>,
+    ) -> io::Result<()> {
+        write!(out, "\"{column}\":{{")?;
+        for (i, v) in values.enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "\"{i}\":{v}")?;
+        }
+        write!(out, "}}")
+    }
+
+    write!(out, "{{")?;
+    write_column(out, "index", pairs.iter().map(|(r, _)| r))?;
+    write!(out, ",")?;
+    write_column(out, "owner", pairs.iter().map(|(_, o)| o))?;
+    write!(out, "}}")?;
+    out.flush()
+}
+
+/// Write the assignment of a table as `(row_id, owner_id)` pairs to a CSV or Parquet file,
+/// chosen by the file extension. Row ids are turned into positions in the source file, which
+/// row `i` with row id `row_ids[i]` has.
+pub fn write_owner_pairs(path: &Path, row_ids: &[u64], pairs: &[(RowId, OwnerId)]) -> Result<()> {
+    let mut positions = vec![0u64; row_ids.len()];
+    for (i, &row_id) in row_ids.iter().enumerate() {
+        positions[row_id as usize] = i as u64;
+    }
+    let mut df = df!(
+        "row_id" => pairs.iter().map(|(r, _)| positions[r.0 as usize]).collect::<Vec<_>>(),
+        "owner_id" => pairs.iter().map(|(_, o)| o.0).collect::<Vec<_>>()
+    )?;
+
+    let f = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => CsvWriter::new(f).finish(&mut df)?,
+        Some("parquet") => ParquetWriter::new(f).finish(&mut df).map(|_| ())?,
+        Some("ipc" | "arrow") => IpcWriter::new(f).finish(&mut df)?,
+        _ => bail!("unsupported assignment format: {}", path.display()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{dataset_dirs, write_csv};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_records_per_copy() {
+        assert_eq!(records_per_copy(1., 2, 10).unwrap(), vec![7, 3]);
+        assert_eq!(records_per_copy(2., 1, 5).unwrap(), vec![5]);
+        assert!(records_per_copy(0., 1, 5).is_err());
+    }
+
+    #[test]
+    fn test_assign() {
+        let tables = ["lineitem", "nation", "orders"];
+        for (equal_owners, equal_records) in [(true, true), (false, false)] {
+            let config = AssignConfig {
+                num_owners: 20,
+                equal_owners,
+                equal_records,
+                ..Default::default()
+            };
+            let assign = |seed| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let owners = assign_owners_to_tables(&mut rng, &tables, &config);
+                let pairs = owners
+                    .iter()
+                    .map(|o| assign_records_to_owners(&mut rng, 100, o, &config).unwrap())
+                    .collect::<Vec<_>>();
+                (owners, pairs)
+            };
+
+            let (owners, pairs) = assign(7);
+            assert_eq!(owners[0].len(), 20);
+            assert_eq!(owners[1].len(), 5);
+            assert_eq!(owners[2].len(), if equal_owners { 20 } else { 10 });
+            for (owners, pairs) in owners.iter().zip(&pairs) {
+                let mut record_owners: HashMap<RowId, HashSet<OwnerId>> = HashMap::new();
+                for (record, owner) in pairs {
+                    record_owners.entry(*record).or_default().insert(*owner);
+                }
+                assert_eq!(record_owners.len(), 100);
+                assert!(record_owners
+                    .values()
+                    .all(|o| !o.is_empty() && o.len() <= config.max_copy));
+                let used: HashSet<_> = pairs.iter().map(|(_, o)| *o).collect();
+                assert_eq!(used, owners.iter().copied().collect());
+            }
+
+            // deterministic given the seed
+            assert_eq!(assign(7), (owners, pairs));
+        }
+    }
+
+    #[test]
+    fn test_write_formats() {
+        let (_dir, data_dir, json_dir) = dataset_dirs();
+        let csv_dir = json_dir.with_file_name("csv");
+        std::fs::create_dir_all(&csv_dir).unwrap();
+        let mut df = df!("k" => (0..50i64).collect::<Vec<_>>()).unwrap();
+        write_csv(data_dir.join("t.csv"), &mut df);
+
+        let config = AssignConfig {
+            num_owners: 8,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let owners = assign_owners_to_tables(&mut rng, &["t"], &config);
+        let pairs = assign_records_to_owners(&mut rng, 50, &owners[0], &config).unwrap();
+        let row_ids = shuffle_row_ids(&mut rng, 50);
+        write_json(&json_dir, "t", &row_ids, &pairs).unwrap();
+        write_owner_pairs(&csv_dir.join("t-owner.csv"), &row_ids, &pairs).unwrap();
+
+        // both formats give every source row the same owners
+        let json = crate::DataSet::load("t", &data_dir, &Some(json_dir)).unwrap();
+        let csv = crate::DataSet::load("t", &data_dir, &Some(csv_dir)).unwrap();
+        let names = |dataset: &crate::DataSet, row_id: u64| -> Vec<String> {
+            let owners = dataset.tables["t"].owner_map.get(RowId(row_id));
+            let mut names: Vec<String> = owners
+                .iter()
+                .map(|&o| dataset.owner_names.display(o))
+                .collect();
+            names.sort();
+            names
+        };
+        for (i, &row_id) in row_ids.iter().enumerate() {
+            assert_eq!(names(&json, row_id), names(&csv, i as u64));
+        }
+    }
+}
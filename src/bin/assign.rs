@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate tracing;
+
+use anyhow::Result;
+use clap::{builder::BoolishValueParser, Parser, ValueEnum};
+use rand::{rngs::StdRng, SeedableRng};
+use shapley_value_decomposition::{assign::*, *};
+use std::{fs, path::PathBuf, time::Instant};
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Input dataset
+    #[clap(short, long, value_parser)]
+    dataset: String,
+
+    /// Input dataset directory (csv, parquet, ipc or tbl tables)
+    #[clap(short = 'c', long, value_parser)]
+    csv_dir: PathBuf,
+
+    /// Output assignment directory
+    #[clap(short = 'f', long, value_parser)]
+    output_dir: PathBuf,
+
+    /// Zipf exponent of the number of records per number of copies
+    #[clap(short, long, default_value_t = 3.0)]
+    alpha: f64,
+
+    /// Zipf exponent of the probability of an owner to get a copy of a record
+    #[clap(short, long, default_value_t = 3.0)]
+    beta: f64,
+
+    /// Number of data owners
+    #[clap(short = 'k', long, default_value_t = 500)]
+    num_owners: usize,
+
+    /// Max number of copies of a record
+    #[clap(short, long, default_value_t = 4)]
+    max_copy: usize,
+
+    /// Give every table all owners (1) or only a few besides lineitem (0)
+    #[clap(short = 'o', long, value_parser = BoolishValueParser::new(), default_value = "1")]
+    equal_owners: bool,
+
+    /// Pick the owners of a record uniformly (1) or by a Zipf distribution (0)
+    #[clap(short = 'r', long, value_parser = BoolishValueParser::new(), default_value = "1")]
+    equal_records: bool,
+
+    /// Random seed
+    #[clap(short, long, default_value_t = 0)]
+    seed: u64,
+
+    /// Output format
+    #[clap(long, value_enum, default_value = "json")]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    /// `<table>-index.json` and `<table>-owner.json`, as `scripts/assign_data.py`
+    Json,
+    /// `<table>-owner.csv` of `(row_id, owner_id)` pairs
+    Csv,
+    /// `<table>-owner.parquet` of `(row_id, owner_id)` pairs
+    Parquet,
+}
+
+fn main() -> Result<()> {
+    utils::init_tracing_subscriber("info")?;
+    let args = Args::parse();
+    info!("args: {:#?}", args);
+
+    let begin = Instant::now();
+    let dataset = DataSet::load(&args.dataset, &args.csv_dir, &None)?;
+    let mut tables: Vec<(&str, usize)> = dataset
+        .tables
+        .values()
+        .map(|t| (t.name.as_str(), t.df.height()))
+        .collect();
+    tables.sort();
+
+    let config = AssignConfig {
+        alpha: args.alpha,
+        beta: args.beta,
+        num_owners: args.num_owners,
+        max_copy: args.max_copy,
+        equal_owners: args.equal_owners,
+        equal_records: args.equal_records,
+        ..Default::default()
+    };
+    fs::create_dir_all(&args.output_dir)?;
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let names: Vec<&str> = tables.iter().map(|(name, _)| *name).collect();
+    let owners = assign_owners_to_tables(&mut rng, &names, &config);
+    for ((name, num_records), owners) in tables.into_iter().zip(owners) {
+        let pairs = assign_records_to_owners(&mut rng, num_records, &owners, &config)?;
+        let row_ids = shuffle_row_ids(&mut rng, num_records);
+        match args.format {
+            Format::Json => write_json(&args.output_dir, name, &row_ids, &pairs)?,
+            Format::Csv => write_owner_pairs(
+                &args.output_dir.join(format!("{name}-owner.csv")),
+                &row_ids,
+                &pairs,
+            )?,
+            Format::Parquet => write_owner_pairs(
+                &args.output_dir.join(format!("{name}-owner.parquet")),
+                &row_ids,
+                &pairs,
+            )?,
+        }
+        info!(
+            "table {name}: {num_records} records, {} owners",
+            owners.len()
+        );
+    }
+
+    info!("done in {:?}", Instant::now() - begin);
+    Ok(())
+}
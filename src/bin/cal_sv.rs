@@ -1,31 +1,143 @@
 #[macro_use]
 extern crate tracing;
 
-use anyhow::{Context, Ok, Result};
+use anyhow::{ensure, Context, Ok, Result};
 use clap::{Parser, ValueEnum};
 use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 use serde_json::json;
-use shapley_value_decomposition::{utils::hashmap_reduce, *};
-use std::{fs::File, io::BufWriter, path::PathBuf, time::Instant};
+use shapley_value_decomposition::{
+    alg::synthesis_sv::recursive_decompose::DecomposeMemo,
+    game_store::{self, GameFileHeader},
+    iso_cache::IsoCache,
+    utils::hashmap_reduce,
+    *,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    mem,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug, Parser)]
 struct Args {
     /// Input dataset
-    #[clap(short = 'd', long, value_parser)]
-    dataset: String,
+    #[clap(short = 'd', long, value_parser, required_unless_present_any = &["games", "exps", "dimacs"])]
+    dataset: Option<String>,
 
-    /// Input dataset file
-    #[clap(short = 'c', long, value_parser)]
-    csv_dir: PathBuf,
+    /// Input dataset directory (csv, parquet or ipc tables)
+    #[clap(short = 'c', long, value_parser, required_unless_present_any = &["games", "exps", "dimacs"])]
+    csv_dir: Option<PathBuf>,
 
     /// Input owner assignment file
     #[clap(short = 'a', long, value_parser)]
     assignment_dir: Option<PathBuf>,
 
+    /// Load the dataset from its binary cache, rebuilt when the input files change
+    #[clap(long)]
+    cache: bool,
+
+    /// Join or union plan file (JSON or TOML). Use the built-in plan of the dataset if not set.
+    #[clap(short = 'p', long, value_parser)]
+    plan: Option<PathBuf>,
+
+    /// SQL query over the tables of the dataset, compiled into the join plan
+    #[clap(short = 'q', long, value_parser, conflicts_with = "plan")]
+    query: Option<String>,
+
+    /// Value the boolean query: merge all join results into a single game
+    #[clap(long)]
+    boolean_query: bool,
+
+    /// Save the generated games to a game file, to be read again via `--games`
+    #[clap(long, value_parser)]
+    save_games: Option<PathBuf>,
+
+    /// Read the games from a game file instead of generating them from the dataset
+    #[clap(
+        long,
+        value_parser,
+        conflicts_with_all = &[
+            "dataset",
+            "csv-dir",
+            "assignment-dir",
+            "cache",
+            "plan",
+            "query",
+            "boolean-query",
+            "save-games",
+        ]
+    )]
+    games: Option<PathBuf>,
+
+    /// Read hand-crafted games from a file of boolean expressions over owner ids, one per line:
+    /// a DNF such as `1 2 + 3 4`, or a JSON expression such as `{"and": [1, {"or": [2, 3]}]}`
+    #[clap(
+        long,
+        value_parser,
+        conflicts_with_all = &[
+            "dataset",
+            "csv-dir",
+            "assignment-dir",
+            "cache",
+            "plan",
+            "query",
+            "boolean-query",
+            "save-games",
+            "games",
+        ]
+    )]
+    exps: Option<PathBuf>,
+
+    /// Read a game from each DIMACS-like hypergraph file, whose variables are owner names
+    #[clap(
+        long,
+        value_parser,
+        multiple_values = true,
+        conflicts_with_all = &[
+            "dataset",
+            "csv-dir",
+            "assignment-dir",
+            "cache",
+            "plan",
+            "query",
+            "boolean-query",
+            "save-games",
+            "games",
+            "exps",
+        ]
+    )]
+    dimacs: Vec<PathBuf>,
+
+    /// Save every game to `<dir>/game-<i>.dnf` in the DIMACS-like hypergraph format
+    #[clap(long, value_parser)]
+    save_dimacs: Option<PathBuf>,
+
     /// Output file
     #[clap(short, long, value_parser)]
     output: PathBuf,
 
+    /// Generate the games in chunks of this many games and reduce their Shapley values chunk by
+    /// chunk, so that the games are never all in memory. The join runs in batches of this many
+    /// rows of the init table. Needs an owner assignment
+    #[clap(
+        long,
+        value_parser,
+        conflicts_with_all = &["games", "exps", "dimacs", "save-games", "save-dimacs"]
+    )]
+    chunk_size: Option<usize>,
+
+    /// Compute the Shapley values of games that are equal up to renaming their owners once
+    #[clap(long)]
+    iso_cache: bool,
+
+    /// Decompose the sub-expressions shared by games and compute their coefficients once
+    /// (rdsv only). The memo keeps every distinct sub-expression, but not the games themselves
+    #[clap(long)]
+    share_subtrees: bool,
+
     /// Method
     #[clap(short, long, value_enum)]
     method: Method,
@@ -61,45 +173,98 @@ fn main() -> Result<()> {
     info!("args: {:#?}", args);
     utils::setup_rayon(args.num_threads)?;
 
+    ensure!(
+        !args.share_subtrees || matches!(args.method, Method::RDSV),
+        "--share-subtrees needs method rdsv"
+    );
+    // without owner assignment, the join is deduplicated as a whole rather than in batches
+    ensure!(
+        args.chunk_size.is_none() || args.assignment_dir.is_some(),
+        "--chunk-size needs an owner assignment"
+    );
+    let plan = args.plan.as_ref().map(Plan::load).transpose()?;
+
     let begin = Instant::now();
+    let caches = Caches {
+        iso_cache: args.iso_cache.then(IsoCache::new),
+        memo: args.share_subtrees.then(DecomposeMemo::new),
+    };
 
     let (result, load_time, sv_cal_time) = polars_core::POOL.install(|| {
+        if let Some(chunk_size) = args.chunk_size {
+            return stream_shapley_values(&args, plan, chunk_size, &caches).unwrap();
+        }
+
         let begin_load = Instant::now();
-        let dataset = DataSet::load(&args.dataset, &args.csv_dir, &args.assignment_dir).unwrap();
-        let load_time = Instant::now() - begin_load;
-        let games = Game::generate_games(&dataset).unwrap();
+        let (games, owner_names, load_time) = match (&args.games, &args.exps) {
+            (Some(games_f), _) => {
+                let (header, games) = game_store::read_games(games_f).unwrap();
+                info!(
+                    "read {} games of dataset {} from {}",
+                    games.len(),
+                    header.dataset,
+                    games_f.display()
+                );
+                (games, header.owner_names(), Instant::now() - begin_load)
+            }
+            (None, Some(exps_f)) => {
+                let games = game_store::read_exps(exps_f).unwrap();
+                info!("read {} games from {}", games.len(), exps_f.display());
+                // owners are reported by their ids
+                (games, OwnerNames::default(), Instant::now() - begin_load)
+            }
+            (None, None) if !args.dimacs.is_empty() => {
+                let mut owner_names = OwnerNames::default();
+                let games = game_store::read_dimacs_games(&args.dimacs, &mut owner_names).unwrap();
+                info!("read {} games from DIMACS files", games.len());
+                (games, owner_names, Instant::now() - begin_load)
+            }
+            (None, None) => generate_games(&args, plan).unwrap(),
+        };
 
         println!(" # of games: {}", &games.len());
 
+        if let Some(dir) = &args.save_dimacs {
+            game_store::write_dimacs_games(dir, &games, &owner_names).unwrap();
+            info!("saved {} games to {}", games.len(), dir.display());
+        }
+
         let begin_cal = Instant::now();
-        let shapley_values = games
-            .into_par_iter()
-            .enumerate()
-            .map(|(i, game)| {
-                if i % 100_000 == 0 {
-                    info!("game: #{}", i);
-                }
-                match args.method {
-                    Method::Traditional => alg::traditional::traditional_method(&game),
-                    Method::Permutation => alg::permutation::permutation_method(
-                        &game,
-                        args.sample_size.context("need sample size").unwrap(),
-                    ),
-                    Method::IUSV => alg::iusv::synthesis_method(&game),
-                    Method::RDSV => alg::proposed::proposed_method(&game),
-                }
-            })
-            .reduce(ShapleyValues::default, hashmap_reduce);
+        let shapley_values = cal_sv(&args, games, 0, &caches);
 
         let sv_cal_time = Instant::now() - begin_cal;
         info!("time in sv_cal {:?}", sv_cal_time);
 
+        let shapley_values = owner_names.name_values(&shapley_values);
         (shapley_values, load_time, sv_cal_time)
     });
 
+    if let Some(iso_cache) = &caches.iso_cache {
+        info!(
+            "iso cache: {} hits, {} misses, {} forms, hit rate {:.4}",
+            iso_cache.hits(),
+            iso_cache.misses(),
+            iso_cache.len(),
+            iso_cache.hit_rate()
+        );
+    }
+    if let Some(memo) = &caches.memo {
+        info!(
+            "shared subtrees: {} hits, {} misses, hit rate {:.4}",
+            memo.hits(),
+            memo.misses(),
+            memo.hit_rate()
+        );
+    }
+
     let total_time = Instant::now() - begin;
     let num_of_owners = result.len();
-    let avg_time = total_time / num_of_owners as u32;
+    // a query without any result has no game, hence no owner
+    let avg_time = if num_of_owners == 0 {
+        Duration::ZERO
+    } else {
+        total_time / num_of_owners as u32
+    };
 
     let sv_result = SVResult {
         shapley_values: result,
@@ -114,8 +279,18 @@ fn main() -> Result<()> {
     result_json.as_object_mut().unwrap().append(
         json!({
             "method": format!("{:?}", args.method).to_lowercase(),
+            "dataset": args.dataset,
             "csv_dir": args.csv_dir,
+            "games": args.games,
+            "exps": args.exps,
+            "dimacs": args.dimacs,
             "assignment_dir": args.assignment_dir,
+            "plan": args.plan,
+            "query": args.query,
+            "boolean_query": args.boolean_query,
+            "chunk_size": args.chunk_size,
+            "iso_cache_hit_rate": caches.iso_cache.as_ref().map(IsoCache::hit_rate),
+            "share_subtrees_hit_rate": caches.memo.as_ref().map(DecomposeMemo::hit_rate),
             "num_threads": args.num_threads,
             "sample_size": args.sample_size,
         })
@@ -128,3 +303,116 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// The caches shared by all games, if enabled.
+struct Caches {
+    iso_cache: Option<IsoCache>,
+    memo: Option<DecomposeMemo>,
+}
+
+/// The Shapley values of `games`, numbered from `offset` in the log.
+fn cal_sv(args: &Args, games: Vec<Game>, offset: usize, caches: &Caches) -> ShapleyValues {
+    games
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, game)| {
+            let i = offset + i;
+            if i % 100_000 == 0 {
+                info!("game: #{}", i);
+            }
+            let method = |game: &Game| match args.method {
+                Method::Traditional => alg::traditional::traditional_method(game),
+                Method::Permutation => alg::permutation::permutation_method(
+                    game,
+                    args.sample_size.context("need sample size").unwrap(),
+                ),
+                Method::IUSV => alg::iusv::synthesis_method(game),
+                Method::RDSV => match &caches.memo {
+                    Some(memo) => memo.cal_sv(game),
+                    None => alg::proposed::proposed_method(game),
+                },
+            };
+            match &caches.iso_cache {
+                Some(iso_cache) => iso_cache.shapley_values(&game, method),
+                None => method(&game),
+            }
+        })
+        .reduce(ShapleyValues::default, hashmap_reduce)
+}
+
+/// Generate the games of the dataset in chunks of `chunk_size` games, and reduce their Shapley
+/// values chunk by chunk. Return the Shapley values, the time to load the dataset and the time
+/// to compute the Shapley values.
+fn stream_shapley_values(
+    args: &Args,
+    plan: Option<Plan>,
+    chunk_size: usize,
+    caches: &Caches,
+) -> Result<(HashMap<String, f64>, Duration, Duration)> {
+    let (dataset, plan, load_time) = load_dataset(args, plan)?;
+    let mut shapley_values = ShapleyValues::default();
+    let mut sv_cal_time = Duration::ZERO;
+    let mut offset = 0;
+    let num_games = Game::generate_game_chunks(&dataset, &plan, chunk_size, |games| {
+        let begin_cal = Instant::now();
+        let num_games = games.len();
+        let chunk_values = cal_sv(args, games, offset, caches);
+        shapley_values = hashmap_reduce(mem::take(&mut shapley_values), chunk_values);
+        offset += num_games;
+        sv_cal_time += Instant::now() - begin_cal;
+        Ok(())
+    })?;
+
+    println!(" # of games: {}", num_games);
+    info!("time in sv_cal {:?}", sv_cal_time);
+    let shapley_values = dataset.owner_names.name_values(&shapley_values);
+    Ok((shapley_values, load_time, sv_cal_time))
+}
+
+/// Load the dataset and resolve its plan. Return the time to load the dataset as well.
+fn load_dataset(args: &Args, plan: Option<Plan>) -> Result<(DataSet, Plan, Duration)> {
+    let dataset_name = args.dataset.as_deref().context("need dataset")?;
+    let csv_dir = args.csv_dir.as_ref().context("need dataset directory")?;
+    let begin_load = Instant::now();
+    let dataset = if args.cache {
+        DataSet::load_cached(dataset_name, csv_dir, &args.assignment_dir)?
+    } else {
+        DataSet::load(dataset_name, csv_dir, &args.assignment_dir)?
+    };
+    let load_time = Instant::now() - begin_load;
+    let mut plan = match (plan, &args.query) {
+        (Some(plan), _) => plan,
+        (None, Some(query)) => Plan::Join(sql::compile(query, &dataset)?),
+        (None, None) => Plan::Join(
+            PLANS
+                .get(dataset_name)
+                .context("cannot find join plan")?
+                .clone(),
+        ),
+    };
+    if args.boolean_query {
+        plan.set_boolean();
+    }
+    Ok((dataset, plan, load_time))
+}
+
+/// Load the dataset and generate its games, saving them if asked to. Return the time to load
+/// the dataset as well.
+fn generate_games(args: &Args, plan: Option<Plan>) -> Result<(Vec<Game>, OwnerNames, Duration)> {
+    let (dataset, plan, load_time) = load_dataset(args, plan)?;
+    let games = Game::generate_games_for(&dataset, &plan)?;
+
+    if let Some(games_f) = &args.save_games {
+        let header = GameFileHeader {
+            dataset: dataset.name.clone(),
+            plan,
+            query: args.query.clone(),
+            boolean_query: args.boolean_query,
+            owner_names: dataset.owner_names.names().to_vec(),
+            num_games: games.len(),
+        };
+        game_store::write_games(games_f, &header, &games)?;
+        info!("saved {} games to {}", games.len(), games_f.display());
+    }
+    Ok((games, dataset.owner_names, load_time))
+}
@@ -6,7 +6,12 @@ use clap::{Parser, ValueEnum};
 use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 use serde_json::json;
 use shapley_value_decomposition::{utils::hashmap_reduce, *};
-use std::{fs::File, io::BufWriter, path::PathBuf, time::Instant};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -14,7 +19,7 @@ struct Args {
     #[clap(short = 'd', long, value_parser)]
     dataset: String,
 
-    /// Input dataset file
+    /// Input dataset directory (csv, parquet or ipc tables)
     #[clap(short = 'c', long, value_parser)]
     csv_dir: PathBuf,
 
@@ -22,6 +27,14 @@ struct Args {
     #[clap(short = 'a', long, value_parser)]
     assignment_dir: Option<PathBuf>,
 
+    /// Load the dataset from its binary cache, rebuilt when the input files change
+    #[clap(long)]
+    cache: bool,
+
+    /// Join or union plan file (JSON or TOML). Use the built-in plan of the dataset if not set.
+    #[clap(short = 'p', long, value_parser)]
+    plan: Option<PathBuf>,
+
     /// Output file
     #[clap(short, long, value_parser)]
     output: PathBuf,
@@ -65,13 +78,27 @@ fn main() -> Result<()> {
     info!("args: {:#?}", args);
     utils::setup_rayon(args.num_threads)?;
 
+    let plan = match &args.plan {
+        Some(path) => Plan::load(path)?,
+        None => Plan::Join(
+            PLANS
+                .get(args.dataset.as_str())
+                .context("cannot find join plan")?
+                .clone(),
+        ),
+    };
+
     let begin = Instant::now();
 
     let (result, load_time, sv_cal_time) = polars_core::POOL.install(|| {
         let begin_load = Instant::now();
-        let dataset = DataSet::load(&args.dataset, &args.csv_dir, &args.assignment_dir).unwrap();
+        let dataset = if args.cache {
+            DataSet::load_cached(&args.dataset, &args.csv_dir, &args.assignment_dir).unwrap()
+        } else {
+            DataSet::load(&args.dataset, &args.csv_dir, &args.assignment_dir).unwrap()
+        };
         let load_time = Instant::now() - begin_load;
-        let games = Game::generate_games(&dataset).unwrap();
+        let games = Game::generate_games_for(&dataset, &plan).unwrap();
 
         println!(" # of games: {}", &games.len());
 
@@ -100,12 +127,18 @@ fn main() -> Result<()> {
         let sv_cal_time = Instant::now() - begin_cal;
         info!("time in sv_cal {:?}", sv_cal_time);
 
+        let shapley_values = dataset.owner_names.name_values(&shapley_values);
         (shapley_values, load_time, sv_cal_time)
     });
 
     let total_time = Instant::now() - begin;
     let num_of_owners = result.len();
-    let avg_time = total_time / num_of_owners as u32;
+    // a query without any result has no game, hence no owner
+    let avg_time = if num_of_owners == 0 {
+        Duration::ZERO
+    } else {
+        total_time / num_of_owners as u32
+    };
 
     let sv_result = SVResult {
         shapley_values: result,
@@ -122,6 +155,7 @@ fn main() -> Result<()> {
             "method": format!("{:?}", args.method).to_lowercase(),
             "csv_dir": args.csv_dir,
             "assignment_dir": args.assignment_dir,
+            "plan": args.plan,
             "num_threads": args.num_threads,
             "sample_size": args.sample_size,
         })
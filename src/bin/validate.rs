@@ -0,0 +1,75 @@
+#[macro_use]
+extern crate tracing;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use shapley_value_decomposition::*;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Input dataset
+    #[clap(short = 'd', long, value_parser)]
+    dataset: String,
+
+    /// Input dataset directory (csv, parquet, ipc or tbl tables)
+    #[clap(short = 'c', long, value_parser)]
+    csv_dir: PathBuf,
+
+    /// Input owner assignment file
+    #[clap(short = 'a', long, value_parser)]
+    assignment_dir: Option<PathBuf>,
+
+    /// Load the dataset from its binary cache, rebuilt when the input files change
+    #[clap(long)]
+    cache: bool,
+
+    /// Join or union plan file (JSON or TOML). Use the built-in plan of the dataset if not set.
+    #[clap(short = 'p', long, value_parser)]
+    plan: Option<PathBuf>,
+
+    /// SQL query over the tables of the dataset, compiled into the join plan
+    #[clap(short = 'q', long, value_parser, conflicts_with = "plan")]
+    query: Option<String>,
+}
+
+fn main() -> Result<()> {
+    utils::init_tracing_subscriber("info")?;
+    let args = Args::parse();
+    info!("args: {:#?}", args);
+
+    let plan = args.plan.as_ref().map(Plan::load).transpose()?;
+    // loading the dataset stops at the first table whose row ids do not fit its rows
+    if let Some(assignment_dir) = &args.assignment_dir {
+        let report = DataSet::validate_files(&args.csv_dir, assignment_dir)?;
+        check_report(&report, &args.dataset)?;
+    }
+    let dataset = if args.cache {
+        DataSet::load_cached(&args.dataset, &args.csv_dir, &args.assignment_dir)?
+    } else {
+        DataSet::load(&args.dataset, &args.csv_dir, &args.assignment_dir)?
+    };
+    let plan = match (plan, &args.query) {
+        (Some(plan), _) => plan,
+        (None, Some(query)) => Plan::Join(sql::compile(query, &dataset)?),
+        (None, None) => Plan::Join(
+            PLANS
+                .get(args.dataset.as_str())
+                .context("cannot find join plan")?
+                .clone(),
+        ),
+    };
+
+    let report = dataset.validate(&plan);
+    check_report(&report, &args.dataset)?;
+    info!("dataset {} is consistent with its plan", args.dataset);
+    Ok(())
+}
+
+fn check_report(report: &validate::ValidationReport, dataset: &str) -> Result<()> {
+    if !report.is_ok() {
+        print!("{report}");
+        bail!("found {} issues in dataset {dataset}", report.issues.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+//! Binary cache of a loaded [`DataSet`], so that repeated runs skip parsing the source tables
+//! and the owner assignment.
+//!
+//! The cache directory holds, for every table, its frame (with row ids) as an uncompressed
+//! Arrow IPC file and its owner map as a compact `.owners` file, both memory-mapped on reload
+//! and read in place rather than copied, along with the owner names of the assignment.
+//! A manifest records the size and mtime of every input file; the cache is rebuilt when they
+//! change.
+
+use crate::{DataSet, OwnerId, OwnerMap, OwnerNames, OwnerSet, Table};
+use anyhow::{ensure, Context, Result};
+use memmap2::Mmap;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Instant, UNIX_EPOCH},
+};
+
+pub const CACHE_DIR_NAME: &str = ".sv-cache";
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const OWNER_NAMES_FILE_NAME: &str = "owner-names.json";
+const OWNERS_MAGIC: &[u8; 8] = b"SVOWNER2";
+
+/// The input files a cache was built from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    has_assignment: bool,
+    /// Size and mtime (in nanoseconds) of every input file.
+    files: BTreeMap<PathBuf, (u64, u128)>,
+    tables: Vec<String>,
+}
+
+impl Manifest {
+    fn new(csv_dir: &Path, meta_dir: &Option<PathBuf>) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for dir in std::iter::once(csv_dir).chain(meta_dir.as_deref()) {
+            for entry in
+                fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+            {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if !metadata.is_file() {
+                    continue;
+                }
+                let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_nanos();
+                files.insert(entry.path(), (metadata.len(), mtime));
+            }
+        }
+        Ok(Self {
+            has_assignment: meta_dir.is_some(),
+            files,
+            tables: vec![],
+        })
+    }
+}
+
+/// The cache directory of a dataset: next to the assignment if there is one, as it depends on
+/// both, otherwise next to the source tables.
+pub fn cache_dir(csv_dir: &Path, meta_dir: &Option<PathBuf>) -> PathBuf {
+    meta_dir.as_deref().unwrap_or(csv_dir).join(CACHE_DIR_NAME)
+}
+
+impl DataSet {
+    /// Load a dataset as [`DataSet::load`], from its cache if it is up to date. Otherwise the
+    /// cache is rebuilt after loading.
+    pub fn load_cached(
+        name: impl Into<String>,
+        csv_dir: impl AsRef<Path>,
+        meta_dir: &Option<PathBuf>,
+    ) -> Result<Self> {
+        let name = name.into();
+        let csv_dir = csv_dir.as_ref();
+        let cache_dir = cache_dir(csv_dir, meta_dir);
+        let mut manifest = Manifest::new(csv_dir, meta_dir)?;
+
+        match read_cache(&name, &cache_dir, &manifest) {
+            Ok(Some(dataset)) => return Ok(dataset),
+            Ok(None) => info!("cache {} is missing or outdated", cache_dir.display()),
+            Err(e) => warn!("failed to read cache {}: {e:#}", cache_dir.display()),
+        }
+
+        let dataset = Self::load(name, csv_dir, meta_dir)?;
+        manifest.tables = dataset.tables.keys().cloned().collect();
+        manifest.tables.sort();
+        if let Err(e) = write_cache(&dataset, &cache_dir, &manifest) {
+            warn!("failed to write cache {}: {e:#}", cache_dir.display());
+        }
+        Ok(dataset)
+    }
+}
+
+fn read_cache(name: &str, cache_dir: &Path, manifest: &Manifest) -> Result<Option<DataSet>> {
+    let manifest_f = cache_dir.join(MANIFEST_FILE_NAME);
+    if !manifest_f.exists() {
+        return Ok(None);
+    }
+    let mut cached: Manifest = serde_json::from_slice(&fs::read(&manifest_f)?)?;
+    let tables = std::mem::take(&mut cached.tables);
+    if &cached != manifest {
+        return Ok(None);
+    }
+
+    let begin = Instant::now();
+    info!("load cache from {}...", cache_dir.display());
+    let mut dataset = DataSet {
+        name: name.to_string(),
+        tables: HashMap::new(),
+        owner_set: OwnerSet::default(),
+        owner_names: OwnerNames::default(),
+    };
+    if manifest.has_assignment {
+        let names: Vec<String> =
+            serde_json::from_slice(&fs::read(cache_dir.join(OWNER_NAMES_FILE_NAME))?)?;
+        dataset.owner_names = names.into_iter().collect();
+    }
+    for table_name in tables {
+        let frame_f = cache_dir.join(format!("{table_name}.ipc"));
+        let df = IpcReader::new(
+            File::open(&frame_f)
+                .with_context(|| format!("failed to open {}", frame_f.display()))?,
+        )
+        .memory_mapped(true)
+        .finish()?;
+        let owner_map = if manifest.has_assignment {
+            read_owner_map(&cache_dir.join(format!("{table_name}.owners")))?
+        } else {
+            OwnerMap::default()
+        };
+        dataset.owner_set.extend(owner_map.owners().iter().copied());
+        dataset.tables.insert(
+            table_name.clone(),
+            Table {
+                name: table_name,
+                df,
+                owner_map,
+            },
+        );
+    }
+    info!("done in {:?}", Instant::now() - begin);
+    Ok(Some(dataset))
+}
+
+fn write_cache(dataset: &DataSet, cache_dir: &Path, manifest: &Manifest) -> Result<()> {
+    let manifest_f = cache_dir.join(MANIFEST_FILE_NAME);
+    fs::create_dir_all(cache_dir)?;
+    // the cache is invalid until it is fully written
+    if manifest_f.exists() {
+        fs::remove_file(&manifest_f)?;
+    }
+    for table in dataset.tables.values() {
+        // files are replaced rather than overwritten, as they may still be mapped
+        let frame_f = cache_dir.join(format!("{}.ipc", table.name));
+        if frame_f.exists() {
+            fs::remove_file(&frame_f)?;
+        }
+        IpcWriter::new(File::create(&frame_f)?).finish(&mut table.df.clone())?;
+        if manifest.has_assignment {
+            let owners_f = cache_dir.join(format!("{}.owners", table.name));
+            if owners_f.exists() {
+                fs::remove_file(&owners_f)?;
+            }
+            write_owner_map(&owners_f, &table.owner_map)?;
+        }
+    }
+    if manifest.has_assignment {
+        fs::write(
+            cache_dir.join(OWNER_NAMES_FILE_NAME),
+            serde_json::to_vec(dataset.owner_names.names())?,
+        )?;
+    }
+    fs::write(&manifest_f, serde_json::to_vec(manifest)?)?;
+    Ok(())
+}
+
+/// Write an owner map as: the magic bytes, the number of row ids `n` and of owners `m`, then
+/// the offsets of the owners of every row id (`n + 1` u64) and the owners (`m` u32), all
+/// little-endian.
+fn write_owner_map(path: &Path, owner_map: &OwnerMap) -> Result<()> {
+    let offsets = owner_map.offsets();
+    let owners = owner_map.owners();
+
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(OWNERS_MAGIC)?;
+    w.write_all(&(offsets.len().saturating_sub(1) as u64).to_le_bytes())?;
+    w.write_all(&(owners.len() as u64).to_le_bytes())?;
+    for offset in offsets {
+        w.write_all(&offset.to_le_bytes())?;
+    }
+    for owner in owners {
+        w.write_all(&owner.0.to_le_bytes())?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Read an owner map written by [`write_owner_map`]. Its arrays are borrowed from the mapped
+/// file, unless the host is big-endian.
+fn read_owner_map(path: &Path) -> Result<OwnerMap> {
+    let f = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    // Safety: cache files are never modified in place, only replaced.
+    let mmap = Arc::new(unsafe { Mmap::map(&f)? });
+    let bytes = &mmap[..];
+    ensure!(
+        bytes.len() >= 24 && &bytes[..8] == OWNERS_MAGIC,
+        "{} is not an owner map",
+        path.display()
+    );
+    let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+    let num_rows = u64_at(8) as usize;
+    let num_owners = u64_at(16) as usize;
+    let offsets_start = 24;
+    let owners_start = offsets_start + 8 * (num_rows + 1);
+    ensure!(
+        bytes.len() == owners_start + 4 * num_owners,
+        "{} is truncated",
+        path.display()
+    );
+
+    if cfg!(target_endian = "little") {
+        return OwnerMap::from_mmap(
+            mmap.clone(),
+            offsets_start..owners_start,
+            owners_start..bytes.len(),
+        )
+        .with_context(|| format!("invalid {}", path.display()));
+    }
+    let offsets = bytes[offsets_start..owners_start]
+        .chunks_exact(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    let owners = bytes[owners_start..]
+        .chunks_exact(4)
+        .map(|b| OwnerId(u32::from_le_bytes(b.try_into().unwrap())))
+        .collect();
+    OwnerMap::from_raw_parts(offsets, owners).with_context(|| format!("invalid {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{dataset_dirs, fixture_dataset, write_csv},
+        RowId,
+    };
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_load_cached() {
+        let (_dir, data_dir, meta_dir) = dataset_dirs();
+        let expect = fixture_dataset();
+        for table in expect.tables.values() {
+            let mut df = table.df.drop(crate::ROW_ID_COL_NAME).unwrap();
+            write_csv(data_dir.join(format!("{}.csv", table.name)), &mut df);
+            let pairs: Vec<(RowId, OwnerId)> = table.owner_map.pairs().collect();
+            let mut pairs = df!(
+                "row_id" => pairs.iter().map(|(r, _)| r.0).collect::<Vec<_>>(),
+                "owner_id" => pairs.iter().map(|(_, o)| o.0).collect::<Vec<_>>()
+            )
+            .unwrap();
+            write_csv(
+                meta_dir.join(format!("{}-owner.csv", table.name)),
+                &mut pairs,
+            );
+        }
+
+        let meta_dir = Some(meta_dir);
+        let cache_dir = cache_dir(&data_dir, &meta_dir);
+        let check = |dataset: &DataSet| {
+            assert_eq!(dataset.owner_names.len(), expect.owner_set.len());
+            for (name, table) in &expect.tables {
+                let pairs: Vec<(RowId, String)> = dataset.tables[name]
+                    .owner_map
+                    .pairs()
+                    .map(|(row_id, owner)| (row_id, dataset.owner_names.display(owner)))
+                    .collect();
+                let expect_pairs: Vec<(RowId, String)> = table
+                    .owner_map
+                    .pairs()
+                    .map(|(row_id, owner)| (row_id, owner.to_string()))
+                    .collect();
+                assert_eq!(pairs, expect_pairs);
+                assert!(dataset.tables[name].df.frame_equal_missing(&table.df));
+            }
+        };
+        let dataset = DataSet::load_cached("test", &data_dir, &meta_dir).unwrap();
+        check(&dataset);
+        assert!(cache_dir.join(MANIFEST_FILE_NAME).exists());
+        assert!(cache_dir.join("orders.owners").exists());
+
+        // loaded from the cache
+        fs::write(cache_dir.join("customer.owners"), "garbage").unwrap();
+        let manifest = Manifest::new(&data_dir, &meta_dir).unwrap();
+        assert!(read_cache("test", &cache_dir, &manifest).is_err());
+        let dataset = DataSet::load_cached("test", &data_dir, &meta_dir).unwrap();
+        check(&dataset);
+        let dataset = read_cache("test", &cache_dir, &manifest).unwrap().unwrap();
+        check(&dataset);
+
+        // invalidated by a change of the source data
+        thread::sleep(Duration::from_millis(10));
+        let mut df = df!("c_custkey" => &[1i64], "c_name" => &["dave"]).unwrap();
+        write_csv(data_dir.join("customer.csv"), &mut df);
+        let mut pairs = df!("row_id" => &[0u64], "owner_id" => &[1u32]).unwrap();
+        write_csv(
+            meta_dir.as_ref().unwrap().join("customer-owner.csv"),
+            &mut pairs,
+        );
+        let manifest = Manifest::new(&data_dir, &meta_dir).unwrap();
+        assert!(read_cache("test", &cache_dir, &manifest).unwrap().is_none());
+        let dataset = DataSet::load_cached("test", &data_dir, &meta_dir).unwrap();
+        assert_eq!(dataset.tables["customer"].df.height(), 1);
+        let dataset = read_cache("test", &cache_dir, &manifest).unwrap().unwrap();
+        assert_eq!(dataset.tables["customer"].df.height(), 1);
+    }
+}
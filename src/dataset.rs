@@ -1,5 +1,5 @@
-use crate::{OwnerSet, Table};
-use anyhow::Result;
+use crate::{OwnerNames, OwnerSet, Table, OWNER_PAIRS_EXTENSIONS, TABLE_EXTENSIONS};
+use anyhow::{bail, Result};
 use glob::glob;
 use std::{
     collections::{HashMap, HashSet},
@@ -12,6 +12,8 @@ pub struct DataSet {
     pub name: String,
     pub tables: HashMap<String, Table>,
     pub owner_set: OwnerSet,
+    /// The names of the owners of the assignment, empty if owners are read from owner columns.
+    pub owner_names: OwnerNames,
 }
 
 impl DataSet {
@@ -38,21 +40,25 @@ impl DataSet {
         info!("load assignment data from {}...", meta_dir.display());
 
         let mut tables = HashMap::new();
-        for csv_f in glob(&csv_dir.join("*.csv").to_string_lossy())? {
-            let csv_f = csv_f?;
-            let name = csv_f.file_stem().unwrap().to_string_lossy().to_string();
-            let row_id_f = meta_dir.join(format!("{name}-index.json"));
-            let owner_f = meta_dir.join(format!("{name}-owner.json"));
-            let table = Table::load(name.clone(), csv_f, row_id_f, owner_f)?;
+        let mut owner_names = OwnerNames::default();
+        for (name, data_f) in table_files(csv_dir)? {
+            let table = match owner_pairs_file(meta_dir, &name) {
+                Some(owner_f) => {
+                    Table::load_with_owner_pairs(name.clone(), data_f, owner_f, &mut owner_names)?
+                }
+                None => {
+                    let row_id_f = meta_dir.join(format!("{name}-index.json"));
+                    let owner_f = meta_dir.join(format!("{name}-owner.json"));
+                    Table::load(name.clone(), data_f, row_id_f, owner_f, &mut owner_names)?
+                }
+            };
             tables.insert(name, table);
         }
         // TODO: store owner list directly
         let owner_set = {
             let mut owners = HashSet::new();
             for t in tables.values() {
-                for s in t.owner_map.values() {
-                    owners.extend(s.iter().copied());
-                }
+                owners.extend(t.owner_map.owners().iter().copied());
             }
             OwnerSet::new(owners.into_iter().collect())
         };
@@ -62,6 +68,7 @@ impl DataSet {
             name: name.into(),
             tables,
             owner_set,
+            owner_names,
         })
     }
 
@@ -71,10 +78,8 @@ impl DataSet {
         info!("load source data from {}...", csv_dir.display());
 
         let mut tables = HashMap::new();
-        for csv_f in glob(&csv_dir.join("*.csv").to_string_lossy())? {
-            let csv_f = csv_f?;
-            let name = csv_f.file_stem().unwrap().to_string_lossy().to_string();
-            let table = Table::load_without_assignment(name.clone(), csv_f)?;
+        for (name, data_f) in table_files(csv_dir)? {
+            let table = Table::load_without_assignment(name.clone(), data_f)?;
             tables.insert(name, table);
         }
 
@@ -83,6 +88,204 @@ impl DataSet {
             name: name.into(),
             tables,
             owner_set: OwnerSet::default(),
+            owner_names: OwnerNames::default(),
         })
     }
 }
+
+/// The owner assignment file of `(row_id, owner_id)` pairs of table `name` in `meta_dir`, if any.
+/// Otherwise the table has the JSON index and owner files of `scripts/assign_data.py`.
+pub(crate) fn owner_pairs_file(meta_dir: &Path, name: &str) -> Option<PathBuf> {
+    OWNER_PAIRS_EXTENSIONS
+        .iter()
+        .map(|ext| meta_dir.join(format!("{name}-owner.{ext}")))
+        .find(|f| f.exists())
+}
+
+/// Find the source table files in `dir`, named by their file stems, sorted by name.
+pub(crate) fn table_files(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files: HashMap<String, PathBuf> = HashMap::new();
+    for ext in TABLE_EXTENSIONS {
+        for f in glob(&dir.join(format!("*.{ext}")).to_string_lossy())? {
+            let f = f?;
+            let name = f.file_stem().unwrap().to_string_lossy().to_string();
+            if let Some(other) = files.insert(name.clone(), f.clone()) {
+                bail!(
+                    "table {name} has more than one source file: {} and {}",
+                    other.display(),
+                    f.display()
+                );
+            }
+        }
+    }
+    let mut files: Vec<(String, PathBuf)> = files.into_iter().collect();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{dataset_dirs, write_csv},
+        OwnerId, RowId,
+    };
+    use polars::prelude::*;
+    use std::fs::{self, File};
+
+    fn owner_names<'a>(
+        dataset: &'a DataSet,
+        owners: impl IntoIterator<Item = &'a OwnerId>,
+    ) -> Vec<&'a str> {
+        let mut names: Vec<&str> = owners
+            .into_iter()
+            .map(|&id| dataset.owner_names.name(id).unwrap())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_load_table_formats() {
+        let (_dir, data_dir, meta_dir) = dataset_dirs();
+
+        let mut df = df!("k" => &[1i64, 2], "v" => &["a", "b"]).unwrap();
+        write_csv(data_dir.join("t1.csv"), &mut df);
+        ParquetWriter::new(File::create(data_dir.join("t2.parquet")).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        IpcWriter::new(File::create(data_dir.join("t3.ipc")).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        for name in ["t1", "t2", "t3"] {
+            fs::write(meta_dir.join(format!("{name}-index.json")), "[1, 0]").unwrap();
+            fs::write(
+                meta_dir.join(format!("{name}-owner.json")),
+                r#"{ "index": { "0": 0, "1": 1, "2": 1 }, "owner": { "0": 1, "1": 2, "2": 3 } }"#,
+            )
+            .unwrap();
+        }
+
+        let dataset = DataSet::load("test", &data_dir, &Some(meta_dir)).unwrap();
+        assert_eq!(dataset.tables.len(), 3);
+        assert_eq!(
+            owner_names(&dataset, dataset.owner_set.iter()),
+            ["1", "2", "3"]
+        );
+        for table in dataset.tables.values() {
+            assert_eq!(table.df.shape(), (2, 3));
+            assert_eq!(
+                table
+                    .df
+                    .column(crate::ROW_ID_COL_NAME)
+                    .unwrap()
+                    .u64()
+                    .unwrap()
+                    .get(0),
+                Some(1)
+            );
+            assert_eq!(
+                owner_names(&dataset, table.owner_map.get(RowId(1))),
+                ["2", "3"]
+            );
+        }
+
+        let dataset = DataSet::load("test", &data_dir, &None).unwrap();
+        assert_eq!(dataset.tables["t2"].df.shape(), (2, 2));
+
+        fs::copy(data_dir.join("t1.csv"), data_dir.join("t1.parquet")).unwrap();
+        assert!(DataSet::load("test", &data_dir, &None).is_err());
+    }
+
+    #[test]
+    fn test_load_owner_pairs() {
+        let (_dir, data_dir, meta_dir) = dataset_dirs();
+
+        let mut df = df!("k" => &[1i64, 2, 3]).unwrap();
+        for name in ["t1", "t2", "t3"] {
+            write_csv(data_dir.join(format!("{name}.csv")), &mut df);
+        }
+        let mut owners = df!(
+            "row_id" => &[0i64, 1, 1, 2],
+            "owner_id" => &[1i64, 2, 3, 1]
+        )
+        .unwrap();
+        write_csv(meta_dir.join("t1-owner.csv"), &mut owners);
+        ParquetWriter::new(File::create(meta_dir.join("t2-owner.parquet")).unwrap())
+            .finish(&mut owners)
+            .unwrap();
+        // the JSON format is still accepted
+        fs::write(meta_dir.join("t3-index.json"), "[2, 1, 0]").unwrap();
+        fs::write(
+            meta_dir.join("t3-owner.json"),
+            r#"{ "index": { "0": 0, "1": 1, "2": 2 }, "owner": { "0": 4, "1": 4, "2": 4 } }"#,
+        )
+        .unwrap();
+
+        let dataset = DataSet::load("test", &data_dir, &Some(meta_dir.clone())).unwrap();
+        assert_eq!(
+            owner_names(&dataset, dataset.owner_set.iter()),
+            ["1", "2", "3", "4"]
+        );
+        for name in ["t1", "t2"] {
+            let table = &dataset.tables[name];
+            assert_eq!(
+                table.df.column(crate::ROW_ID_COL_NAME).unwrap().get(2),
+                AnyValue::UInt64(2)
+            );
+            assert_eq!(owner_names(&dataset, table.owner_map.get(RowId(0))), ["1"]);
+            assert_eq!(
+                owner_names(&dataset, table.owner_map.get(RowId(1))),
+                ["2", "3"]
+            );
+        }
+        assert_eq!(
+            dataset.tables["t3"]
+                .df
+                .column(crate::ROW_ID_COL_NAME)
+                .unwrap()
+                .get(0),
+            AnyValue::UInt64(2)
+        );
+
+        fs::write(meta_dir.join("t3-index.json"), "[2, 1]").unwrap();
+        let err = DataSet::load("test", &data_dir, &Some(meta_dir.clone())).unwrap_err();
+        assert!(err.to_string().contains("has 2 row ids but"));
+        fs::write(meta_dir.join("t3-index.json"), "[2, 1, 0]").unwrap();
+
+        fs::write(meta_dir.join("t1-owner.csv"), "row_id,owner\n0,1\n").unwrap();
+        let err = DataSet::load("test", &data_dir, &Some(meta_dir)).unwrap_err();
+        assert!(err.to_string().contains("cannot find column owner_id"));
+    }
+
+    #[test]
+    fn test_load_owner_names() {
+        let (_dir, data_dir, meta_dir) = dataset_dirs();
+
+        for name in ["t1", "t2"] {
+            fs::write(data_dir.join(format!("{name}.csv")), "k\n1\n2\n").unwrap();
+        }
+        fs::write(
+            meta_dir.join("t1-owner.csv"),
+            "row_id,owner_id\n0,acme\n1,globex\n1,acme\n",
+        )
+        .unwrap();
+        fs::write(meta_dir.join("t2-index.json"), "[0, 1]").unwrap();
+        fs::write(
+            meta_dir.join("t2-owner.json"),
+            r#"{ "index": { "0": 0, "1": 1 }, "owner": { "0": "initech", "1": "acme" } }"#,
+        )
+        .unwrap();
+
+        let dataset = DataSet::load("test", &data_dir, &Some(meta_dir)).unwrap();
+        assert_eq!(dataset.owner_names.len(), 3);
+        assert_eq!(dataset.owner_set.len(), 3);
+        let acme = dataset.owner_names.id("acme").unwrap();
+        assert_eq!(dataset.tables["t1"].owner_map.get(RowId(0)), [acme]);
+        assert_eq!(dataset.tables["t2"].owner_map.get(RowId(1)), [acme]);
+        assert_eq!(
+            owner_names(&dataset, dataset.tables["t1"].owner_map.get(RowId(1))),
+            ["acme", "globex"]
+        );
+    }
+}
@@ -1,14 +1,20 @@
 //! Ref: Jan C. Bioch, Modular Decomposition of Boolean Functions, 2002
 #![allow(clippy::module_inception)]
 
+mod bool_exp;
+mod canonical;
 mod decompose;
+mod dimacs;
 mod dnf;
 mod implicant;
 mod modular_closure;
 pub(crate) mod recursive_decompose;
-mod unionfind;
+pub(crate) mod unionfind;
 mod utils;
 
+pub use bool_exp::BoolExp;
+pub use canonical::{canonicalize, Canonical};
+pub(crate) use decompose::decompose_inner;
 pub use decompose::{decompose, Decompose, SubExp};
 pub use dnf::Dnf;
 pub use implicant::Implicant;
@@ -0,0 +1,178 @@
+use super::{recursive_decompose, Dnf, RecursiveDecompose, Var};
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, str::FromStr};
+
+/// A nested boolean expression, e.g. `{"and": [1, {"or": [2, 3]}]}` in JSON.
+///
+/// An empty `and` is TRUE, and an empty `or` is FALSE.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BoolExp<T> {
+    And { and: Vec<BoolExp<T>> },
+    Or { or: Vec<BoolExp<T>> },
+    Elm(T),
+}
+
+impl<T> FromStr for BoolExp<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+impl<T: Var> BoolExp<T> {
+    /// Expand to a minimized DNF.
+    pub fn to_dnf(&self) -> Dnf<T> {
+        let mut exp = match self {
+            Self::Elm(var) => Dnf::single_variable_exp(var.clone()),
+            Self::And { and } => and
+                .iter()
+                .fold(Dnf::true_exp(), |acc, sub_exp| acc & sub_exp.to_dnf()),
+            Self::Or { or } => or
+                .iter()
+                .fold(Dnf::false_exp(), |acc, sub_exp| acc | sub_exp.to_dnf()),
+        };
+        exp.minimize();
+        exp
+    }
+
+    /// All variables of the expression.
+    pub fn all_variables(&self) -> BTreeSet<T> {
+        let mut vars = BTreeSet::new();
+        self.visit_variables(&mut |var| {
+            vars.insert(var.clone());
+        });
+        vars
+    }
+
+    /// Whether every variable occurs once.
+    pub fn is_read_once(&self) -> bool {
+        let mut vars = BTreeSet::new();
+        let mut read_once = true;
+        self.visit_variables(&mut |var| read_once &= vars.insert(var));
+        read_once
+    }
+
+    fn visit_variables<'a>(&'a self, f: &mut impl FnMut(&'a T)) {
+        match self {
+            Self::Elm(var) => f(var),
+            Self::And { and: list } | Self::Or { or: list } => {
+                list.iter().for_each(|sub_exp| sub_exp.visit_variables(f))
+            }
+        }
+    }
+
+    /// Decompose the expression, or `None` if it is TRUE or FALSE.
+    ///
+    /// A read-once expression is its own decomposition, so it is converted directly without
+    /// expanding it to DNF, which may be exponentially larger. Otherwise its DNF is decomposed.
+    pub fn to_decompose(&self) -> Option<RecursiveDecompose<T>> {
+        if self.is_read_once() {
+            return self.to_decompose_read_once();
+        }
+        let exp = self.to_dnf();
+        if exp.is_true() || exp.is_false() {
+            return None;
+        }
+        Some(recursive_decompose(&exp, &exp.all_variables()))
+    }
+
+    fn to_decompose_read_once(&self) -> Option<RecursiveDecompose<T>> {
+        let (list, is_and) = match self {
+            Self::Elm(var) => return Some(RecursiveDecompose::Var(var.clone())),
+            Self::And { and } => (and, true),
+            Self::Or { or } => (or, false),
+        };
+
+        let mut children = Vec::with_capacity(list.len());
+        for sub_exp in list {
+            match sub_exp.to_decompose_read_once() {
+                // flatten nested operators of the same kind
+                Some(RecursiveDecompose::And(sub_list)) if is_and => children.extend(sub_list),
+                Some(RecursiveDecompose::Or(sub_list)) if !is_and => children.extend(sub_list),
+                Some(d) => children.push(d),
+                // a constant sub-expression: TRUE is the identity of AND and absorbs OR
+                None => match (sub_exp.to_dnf().is_true(), is_and) {
+                    (true, true) | (false, false) => {}
+                    (true, false) | (false, true) => return None,
+                },
+            }
+        }
+        match children.len() {
+            0 => None,
+            1 => children.pop(),
+            _ if is_and => Some(RecursiveDecompose::And(children)),
+            _ => Some(RecursiveDecompose::Or(children)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dnf;
+
+    #[test]
+    fn test_parse() {
+        let exp: BoolExp<i32> = r#"{"and": [1, {"or": [2, 3]}]}"#.parse().unwrap();
+        assert_eq!(
+            exp,
+            BoolExp::And {
+                and: vec![
+                    BoolExp::Elm(1),
+                    BoolExp::Or {
+                        or: vec![BoolExp::Elm(2), BoolExp::Elm(3)]
+                    }
+                ]
+            }
+        );
+        assert!(r#"{"xor": [1, 2]}"#.parse::<BoolExp<i32>>().is_err());
+    }
+
+    #[test]
+    fn test_to_dnf() {
+        let exp: BoolExp<i32> = r#"{"and": [1, {"or": [2, 3]}]}"#.parse().unwrap();
+        assert_eq!(exp.to_dnf(), dnf!(1 2 + 1 3));
+        assert!(exp.is_read_once());
+
+        let exp: BoolExp<i32> = r#"{"or": [{"and": [1, 2]}, 1, 3]}"#.parse().unwrap();
+        assert_eq!(exp.to_dnf(), dnf!(1 + 3));
+        assert!(!exp.is_read_once());
+
+        let exp: BoolExp<i32> = r#"{"and": []}"#.parse().unwrap();
+        assert_eq!(exp.to_dnf(), dnf!(true));
+        let exp: BoolExp<i32> = r#"{"or": []}"#.parse().unwrap();
+        assert_eq!(exp.to_dnf(), dnf!(false));
+    }
+
+    #[test]
+    fn test_to_decompose() {
+        for s in [
+            "1",
+            r#"{"and": [1, {"or": [2, 3]}]}"#,
+            r#"{"or": [{"and": [1, {"and": [2, 4]}]}, {"or": [3, 5]}]}"#,
+            r#"{"or": [{"and": [1, 2]}, {"and": [2, 3]}, {"and": [3, 4]}]}"#,
+            r#"{"and": [{"and": []}, {"or": [1, 2]}]}"#,
+        ] {
+            let exp: BoolExp<i32> = s.parse().unwrap();
+            let dnf = exp.to_dnf();
+            let d = exp.to_decompose().unwrap();
+            assert_eq!(d, recursive_decompose(&dnf, &dnf.all_variables()), "{s}");
+            assert_eq!(d.expand(), dnf, "{s}");
+        }
+
+        for s in [
+            r#"{"and": []}"#,
+            r#"{"or": [{"or": []}]}"#,
+            r#"{"or": [1, {"and": []}]}"#,
+        ] {
+            let exp: BoolExp<i32> = s.parse().unwrap();
+            assert_eq!(exp.to_decompose(), None, "{s}");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use super::{Dnf, Var};
+
+/// Upper bound of the leaves of the search tree explored by [`canonicalize`]. Beyond it, the
+/// smallest form found so far is kept. It is still a relabeling of the DNF, so isomorphic DNFs
+/// may get different forms, but different DNFs never get the same one.
+const MAX_LEAVES: usize = 64;
+
+/// A DNF relabeled to its canonical form. DNFs that are equal up to renaming their variables
+/// have the same canonical form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Canonical<T: Var> {
+    /// The relabeled DNF, over variables `0..vars.len()`.
+    pub exp: Dnf<u32>,
+    /// The variable relabeled to `i`, for every `i`.
+    pub vars: Vec<T>,
+}
+
+/// Relabel `exp` to its canonical form.
+///
+/// The variables are partitioned by color refinement: two variables have the same color as long
+/// as the colors of the implicants they occur in cannot tell them apart. Ties are broken by
+/// individualizing every variable of the first non-singleton color in turn, and the smallest
+/// relabeled DNF over all discrete colorings is the canonical form. Variables occurring in the
+/// same implicants are interchangeable, so only one of them is individualized.
+pub fn canonicalize<T: Var>(exp: &Dnf<T>) -> Canonical<T> {
+    let vars: Vec<T> = exp.all_variables().into_iter().collect();
+    let terms: Vec<Vec<usize>> = exp
+        .iter()
+        .map(|t| t.iter().map(|v| vars.binary_search(v).unwrap()).collect())
+        .collect();
+    let mut occurrences = vec![vec![]; vars.len()];
+    for (i, term) in terms.iter().enumerate() {
+        for &v in term {
+            occurrences[v].push(i);
+        }
+    }
+
+    let shape = Shape { terms, occurrences };
+    let mut search = Search {
+        leaves: 0,
+        best: None,
+    };
+    shape.search(shape.refine(vec![0; vars.len()]), &mut search);
+
+    let (exp, colors) = search.best.unwrap();
+    let mut vars: Vec<_> = vars.into_iter().zip(colors).collect();
+    vars.sort_by_key(|(_, color)| *color);
+    Canonical {
+        exp,
+        vars: vars.into_iter().map(|(var, _)| var).collect(),
+    }
+}
+
+/// A DNF over variables `0..occurrences.len()`.
+struct Shape {
+    terms: Vec<Vec<usize>>,
+    /// The implicants every variable occurs in.
+    occurrences: Vec<Vec<usize>>,
+}
+
+struct Search {
+    leaves: usize,
+    /// The smallest relabeled DNF so far, and its coloring.
+    best: Option<(Dnf<u32>, Vec<u32>)>,
+}
+
+impl Shape {
+    /// Refine `colors` until the colors of the implicants every variable occurs in no longer
+    /// split any color. The refined colors are ranked `0..`, in an order refining `colors`.
+    fn refine(&self, mut colors: Vec<u32>) -> Vec<u32> {
+        let mut num_colors = 0;
+        loop {
+            let signatures: Vec<(u32, Vec<Vec<u32>>)> = self
+                .occurrences
+                .iter()
+                .enumerate()
+                .map(|(v, occurrences)| {
+                    let mut terms: Vec<Vec<u32>> = occurrences
+                        .iter()
+                        .map(|&i| {
+                            let mut term: Vec<u32> =
+                                self.terms[i].iter().map(|&u| colors[u]).collect();
+                            term.sort_unstable();
+                            term
+                        })
+                        .collect();
+                    terms.sort_unstable();
+                    (colors[v], terms)
+                })
+                .collect();
+
+            let mut distinct = signatures.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            colors = signatures
+                .iter()
+                .map(|s| distinct.binary_search(s).unwrap() as u32)
+                .collect();
+            if distinct.len() == num_colors {
+                return colors;
+            }
+            num_colors = distinct.len();
+        }
+    }
+
+    fn search(&self, colors: Vec<u32>, search: &mut Search) {
+        if search.leaves >= MAX_LEAVES {
+            return;
+        }
+
+        let mut sizes = vec![0; colors.len()];
+        for &color in &colors {
+            sizes[color as usize] += 1;
+        }
+        let Some(cell) = sizes.iter().position(|&size| size > 1) else {
+            search.leaves += 1;
+            let exp: Dnf<u32> = self
+                .terms
+                .iter()
+                .map(|term| term.iter().map(|&v| colors[v]).collect())
+                .collect();
+            if search.best.as_ref().is_none_or(|(best, _)| exp < *best) {
+                search.best = Some((exp, colors));
+            }
+            return;
+        };
+
+        let mut tried: Vec<&[usize]> = vec![];
+        for v in (0..colors.len()).filter(|&v| colors[v] as usize == cell) {
+            // swapping variables occurring in the same implicants maps the DNF to itself
+            if tried.contains(&self.occurrences[v].as_slice()) {
+                continue;
+            }
+            tried.push(&self.occurrences[v]);
+
+            let individualized = colors
+                .iter()
+                .enumerate()
+                .map(|(u, &color)| if u == v { 2 * color } else { 2 * color + 1 })
+                .collect();
+            self.search(self.refine(individualized), search);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dnf;
+
+    #[test]
+    fn test_canonicalize() {
+        let exp = dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5);
+        let canonical = canonicalize(&exp);
+        assert_eq!(canonical.exp.len(), exp.len());
+        assert_eq!(
+            canonical.exp.map_variable(|&i| canonical.vars[i as usize]),
+            exp
+        );
+
+        // isomorphic DNFs have the same form
+        for (exp, isomorphic) in [
+            (
+                dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5),
+                dnf!(9 8 6 + 9 8 7 + 8 5 6 + 8 5 7 + 6 7),
+            ),
+            (dnf!(1 2 3 4), dnf!(10 20 30 40)),
+            (dnf!(1 3 + 1 4 + 2 3 + 2 4), dnf!(7 5 + 7 6 + 8 5 + 8 6)),
+            (dnf!(1 + 2 3 + 3 4 5), dnf!(9 + 7 8 + 8 6 5)),
+            (
+                dnf!(1 2 + 2 3 + 3 4 + 4 5 + 5 1),
+                dnf!(3 1 + 1 5 + 5 2 + 2 4 + 4 3),
+            ),
+        ] {
+            assert_eq!(canonicalize(&exp).exp, canonicalize(&isomorphic).exp);
+        }
+
+        // non-isomorphic DNFs do not
+        assert_ne!(
+            canonicalize(&dnf!(1 2 + 2 3 + 3 4)).exp,
+            canonicalize(&dnf!(1 2 + 1 3 + 1 4)).exp
+        );
+        assert_eq!(canonicalize(&dnf!(false)).exp, Dnf::false_exp());
+        assert_eq!(canonicalize(&dnf!(true)).exp, Dnf::true_exp());
+    }
+}
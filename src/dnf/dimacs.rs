@@ -0,0 +1,184 @@
+//! A DIMACS-like hypergraph format of monotone DNFs, to exchange games with external tools, e.g.
+//! model counters and hypergraph dualizers:
+//!
+//! ```text
+//! c v 1 acme
+//! c v 2 globex
+//! c v 3 initech
+//! p dnf 3 2
+//! 1 2 0
+//! 3 0
+//! ```
+//!
+//! Variables are numbered from 1 and every implicant (hyperedge) is terminated by `0`. The
+//! comment lines `c v <index> <variable>` map the numbers to the variables; without them a
+//! number is read as the variable itself. A TRUE expression has a single empty implicant.
+
+use super::{Dnf, Implicant, Var};
+use anyhow::{bail, ensure, Context, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::{BufRead, Write},
+    str::FromStr,
+};
+
+impl<T: Var + fmt::Display> Dnf<T> {
+    /// Write the expression in the DIMACS-like format, with the variable mapping header.
+    pub fn write_dimacs(&self, mut w: impl Write) -> Result<()> {
+        let vars = self.all_variables();
+        let mut index = BTreeMap::new();
+        for (i, var) in vars.iter().enumerate() {
+            writeln!(w, "c v {} {var}", i + 1)?;
+            index.insert(var, i + 1);
+        }
+        writeln!(w, "p dnf {} {}", vars.len(), self.len())?;
+        for implicant in self.iter() {
+            for var in implicant.iter() {
+                write!(w, "{} ", index[var])?;
+            }
+            writeln!(w, "0")?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Dnf<T>
+where
+    T: Var + FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    /// Read an expression in the DIMACS-like format. Implicants may span lines. The result is
+    /// not minimized.
+    pub fn read_dimacs(r: impl BufRead) -> Result<Self> {
+        let mut vars: HashMap<usize, T> = HashMap::new();
+        let mut header = None;
+        let mut exp = Self::new();
+        let mut implicant = Implicant::new();
+        let mut num_implicants = 0;
+        for (i, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix('c') {
+                if let Some((index, var)) = comment
+                    .trim_start()
+                    .strip_prefix("v ")
+                    .and_then(|mapping| mapping.trim().split_once(' '))
+                {
+                    let index = parse_index(index).with_context(|| format!("line {}", i + 1))?;
+                    let var = var
+                        .trim()
+                        .parse()
+                        .with_context(|| format!("line {}: invalid variable {var:?}", i + 1))?;
+                    ensure!(
+                        vars.insert(index, var).is_none(),
+                        "line {}: variable {index} is mapped twice",
+                        i + 1
+                    );
+                }
+                continue;
+            }
+            if let Some(problem) = line.strip_prefix('p') {
+                let fields: Vec<&str> = problem.split_whitespace().collect();
+                let [format, num_vars, num_implicants] = fields[..] else {
+                    bail!("line {}: invalid problem line {line:?}", i + 1);
+                };
+                ensure!(
+                    format == "dnf",
+                    "line {}: unsupported format {format:?}",
+                    i + 1
+                );
+                ensure!(header.is_none(), "line {}: duplicated problem line", i + 1);
+                header = Some((parse_index(num_vars)?, parse_index(num_implicants)?));
+                continue;
+            }
+            for literal in line.split_whitespace() {
+                ensure!(
+                    header.is_some(),
+                    "line {}: implicant before the problem line",
+                    i + 1
+                );
+                let index = parse_index(literal).with_context(|| format!("line {}", i + 1))?;
+                if index == 0 {
+                    exp.insert(std::mem::take(&mut implicant));
+                    num_implicants += 1;
+                    continue;
+                }
+                let var = match vars.get(&index) {
+                    Some(var) => var.clone(),
+                    None => literal
+                        .parse()
+                        .with_context(|| format!("line {}: unmapped variable {index}", i + 1))?,
+                };
+                implicant.insert(var);
+            }
+        }
+
+        let (num_vars, expected) = header.context("missing problem line")?;
+        ensure!(implicant.is_empty(), "the last implicant is not terminated");
+        ensure!(
+            num_implicants == expected,
+            "found {num_implicants} implicants but the problem line declares {expected}"
+        );
+        let found = exp.all_variables().len();
+        ensure!(
+            found <= num_vars,
+            "found {found} variables but the problem line declares {num_vars}"
+        );
+        Ok(exp)
+    }
+}
+
+/// Parse a variable index; negative literals are rejected as the expression is monotone.
+fn parse_index(s: &str) -> Result<usize> {
+    ensure!(
+        !s.starts_with('-'),
+        "negative literal {s} in a monotone expression"
+    );
+    s.parse().with_context(|| format!("invalid number {s:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dnf;
+
+    #[test]
+    fn test_dimacs() {
+        for exp in [
+            dnf!(),
+            dnf!(true),
+            dnf!(1),
+            dnf!(1 + 2 3),
+            dnf!(4 7 + 7 9 + 12),
+        ] {
+            let mut buf = vec![];
+            exp.write_dimacs(&mut buf).unwrap();
+            assert_eq!(Dnf::<i32>::read_dimacs(&buf[..]).unwrap(), exp);
+        }
+
+        let mut buf = vec![];
+        dnf!(4 7 + 9).write_dimacs(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "c v 1 4\nc v 2 7\nc v 3 9\np dnf 3 2\n3 0\n1 2 0\n"
+        );
+
+        // without mapping, implicants spanning lines
+        let exp: Dnf<String> =
+            Dnf::read_dimacs("c an external game\np dnf 3 2\n1\n2 0 3 0\n".as_bytes()).unwrap();
+        assert_eq!(exp.to_string(), "3 + 1 2");
+
+        for (s, err) in [
+            ("1 0\n", "before the problem line"),
+            ("p cnf 1 1\n1 0\n", "unsupported format"),
+            ("p dnf 2 1\n1 -2 0\n", "negative literal"),
+            ("p dnf 2 2\n1 2 0\n", "declares 2"),
+            ("p dnf 2 1\n1 2\n", "not terminated"),
+            ("c v 1 a\nc v 1 b\np dnf 1 1\n1 0\n", "mapped twice"),
+        ] {
+            let e = Dnf::<String>::read_dimacs(s.as_bytes()).unwrap_err();
+            assert!(format!("{e:#}").contains(err), "{s:?}: {e:#}");
+        }
+    }
+}
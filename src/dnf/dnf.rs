@@ -1,9 +1,11 @@
 use super::{utils::*, Implicant, Var};
+use anyhow::{bail, Context, Error, Result};
 use rayon::prelude::*;
 use std::{
     collections::BTreeSet,
     fmt, mem,
     ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+    str::FromStr,
 };
 
 /// A boolean expression in DNF.
@@ -15,6 +17,7 @@ use std::{
     Clone,
     PartialEq,
     Eq,
+    Hash,
     PartialOrd,
     Ord,
     derive_more::Deref,
@@ -91,6 +94,30 @@ impl<T: Var> Dnf<T> {
         *self = ans;
     }
 
+    /// Insert an implicant into a minimized expression, keeping it minimized.
+    ///
+    /// Return false if the implicant is absorbed by an existing one.
+    pub fn insert_minimal(&mut self, term: Implicant<T>) -> bool {
+        if self.iter().any(|t| t.is_subset(&term)) {
+            return false;
+        }
+        self.retain(|t| !term.is_subset(t));
+        self.insert(term)
+    }
+
+    /// OR another minimized expression into this minimized one, keeping it minimized.
+    pub fn extend_minimal(&mut self, other: Dnf<T>) {
+        let (mut to_mutate, to_consume) = if self.len() < other.len() {
+            (other, mem::take(self))
+        } else {
+            (mem::take(self), other)
+        };
+        for term in to_consume {
+            to_mutate.insert_minimal(term);
+        }
+        *self = to_mutate;
+    }
+
     /// Eval to TRUE or FALSE.
     pub fn eval(&self, input_set: &BTreeSet<T>, input_is_true: bool) -> bool {
         self.par_iter().any(|t| t.eval(input_set, input_is_true))
@@ -200,6 +227,36 @@ impl<T: Var + fmt::Display> fmt::Display for Dnf<T> {
     }
 }
 
+/// Parse the [`Display`](fmt::Display) format of a DNF, e.g. `1 2 + 3 4`, `TRUE` or `FALSE`.
+/// The result is not minimized.
+impl<T> FromStr for Dnf<T>
+where
+    T: Var + FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "FALSE" {
+            return Ok(Self::false_exp());
+        }
+        s.split('+')
+            .map(|term| match term.trim() {
+                "" => bail!("empty implicant in {s:?}"),
+                "TRUE" => Ok(Implicant::new()),
+                term => term
+                    .split_whitespace()
+                    .map(|var| {
+                        var.parse()
+                            .with_context(|| format!("invalid variable {var:?}"))
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
 impl<T: Var> BitAnd for Dnf<T> {
     type Output = Dnf<T>;
 
@@ -297,7 +354,7 @@ macro_rules! dnf {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dnf;
+    use crate::implicant;
 
     #[test]
     fn test_display() {
@@ -309,6 +366,24 @@ mod tests {
         assert_eq!("1 + 2 3", format!("{}", dnf!(1 + 2 3)));
     }
 
+    #[test]
+    fn test_parse() {
+        for exp in [
+            dnf!(),
+            dnf!(true),
+            dnf!(1),
+            dnf!(1 2),
+            dnf!(1 + 2 3),
+            dnf!(1 2 + 2 3 4),
+        ] {
+            assert_eq!(exp.to_string().parse::<Dnf<i32>>().unwrap(), exp);
+        }
+        assert_eq!(" 2  1 +3 ".parse::<Dnf<i32>>().unwrap(), dnf!(1 2 + 3));
+        assert_eq!("1 2 + 1".parse::<Dnf<i32>>().unwrap(), dnf!(1 + 1 2));
+        assert!("1 + ".parse::<Dnf<i32>>().is_err());
+        assert!("1 x".parse::<Dnf<i32>>().is_err());
+    }
+
     #[test]
     fn test_is_true_is_false() {
         assert!(dnf!(true).is_true());
@@ -325,6 +400,22 @@ mod tests {
         assert_eq!(actual, expect);
     }
 
+    #[test]
+    fn test_insert_minimal() {
+        let mut actual = dnf!();
+        for term in dnf!(1 2 3 + 4 5 6 + 1 2 + 4 6 + 5 6 7 8 9 + 6 8 + 1 2 3) {
+            actual.insert_minimal(term);
+        }
+        assert_eq!(actual, dnf!(1 2 + 4 6 + 6 8));
+        assert!(!actual.insert_minimal(implicant!(1 2 5)));
+        assert!(actual.insert_minimal(implicant!(6)));
+        assert_eq!(actual, dnf!(1 2 + 6));
+
+        let mut actual = dnf!(1 2 + 3 4);
+        actual.extend_minimal(dnf!(1 + 3 4 5 + 6 7 + 8));
+        assert_eq!(actual, dnf!(1 + 3 4 + 6 7 + 8));
+    }
+
     #[test]
     fn test_arithmetic() {
         assert_eq!(dnf!(1 2), dnf!(1) & dnf!(2));
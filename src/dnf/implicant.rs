@@ -13,6 +13,7 @@ use std::{
     Clone,
     PartialEq,
     Eq,
+    Hash,
     derive_more::Deref,
     derive_more::DerefMut,
     derive_more::AsRef,
@@ -199,7 +200,7 @@ macro_rules! implicant {
 
 #[cfg(test)]
 mod tests {
-    use crate::{dnf, implicant};
+    use crate::dnf;
 
     #[test]
     fn test_display() {
@@ -70,6 +70,43 @@ impl<T: Var> PartialEq for RecursiveDecompose<T> {
 impl<T: Var> Eq for RecursiveDecompose<T> {}
 
 impl<T: Var> RecursiveDecompose<T> {
+    /// All variables of the decomposition.
+    pub fn all_variables(&self) -> BTreeSet<T> {
+        match self {
+            Self::Var(var) => BTreeSet::from([var.clone()]),
+            Self::And(list) | Self::Or(list) | Self::Hybrid { sub_exps: list, .. } => {
+                list.iter().flat_map(Self::all_variables).collect()
+            }
+        }
+    }
+
+    /// The number of implicants of the expansion before it is minimized, saturating at
+    /// `usize::MAX`. It bounds the size of [`RecursiveDecompose::expand`] without expanding.
+    pub fn expanded_len(&self) -> usize {
+        match self {
+            Self::Var(_) => 1,
+            Self::And(list) => list
+                .iter()
+                .map(Self::expanded_len)
+                .fold(1, usize::saturating_mul),
+            Self::Or(list) => list
+                .iter()
+                .map(Self::expanded_len)
+                .fold(0, usize::saturating_add),
+            Self::Hybrid {
+                hybrid_exp,
+                sub_exps,
+            } => hybrid_exp
+                .iter()
+                .map(|t| {
+                    t.iter()
+                        .map(|&i| sub_exps[i].expanded_len())
+                        .fold(1, usize::saturating_mul)
+                })
+                .fold(0, usize::saturating_add),
+        }
+    }
+
     /// Expand to DNF.
     pub fn expand(self) -> Dnf<T> {
         match self {
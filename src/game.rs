@@ -1,23 +1,33 @@
 use crate::{
-    alg::join::join,
-    dnf::{Dnf, Implicant},
+    alg::join::{join, join_batches},
+    dnf::{recursive_decompose, Dnf, Implicant, RecursiveDecompose},
     owner::{OwnerId, OwnerSet},
-    utils::{cartesian_product, dnf_to_syns},
-    DataSet, RowId, PLANS, ROW_ID_COL_NAME,
+    utils::{cartesian_product, dnf_to_syns, factored_product},
+    DataSet, JoinPlan, Plan, RowId, Table, UnionPlan, ROW_ID_COL_NAME,
 };
-use anyhow::{Context, Error, Ok, Result};
+use anyhow::{bail, ensure, Context, Ok, Result};
 use polars_core::{
-    prelude::{AnyValue, DataFrame, NamedFrom},
-    series::{ChunkCompare, Series},
+    frame::groupby::GroupsIndicator,
+    prelude::{
+        AnyValue, DataFrame, DataType, IdxCa, IdxSize, NamedFrom, TakeRandom, UInt64Chunked,
+    },
+    series::Series,
+};
+use rayon::prelude::{
+    IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator,
+};
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap},
+    sync::OnceLock,
 };
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use serde::{Deserialize, Serialize};
-use std::{collections::BTreeSet, str::FromStr};
 
 /// A simple game among data owners.
+///
+/// A game built from its decomposition keeps it, and expands its DNF only when it is asked for.
 #[derive(Debug, Clone)]
 pub struct Game {
-    pub dnf: Dnf<OwnerId>,
+    dnf: OnceLock<Dnf<OwnerId>>,
+    factored: Option<RecursiveDecompose<OwnerId>>,
     pub owner_set: OwnerSet,
 }
 
@@ -25,261 +35,857 @@ impl Game {
     pub fn new(exp: Dnf<OwnerId>) -> Self {
         let owner_set = exp.all_variables().into();
         Self {
-            dnf: exp,
+            dnf: OnceLock::from(exp),
+            factored: None,
+            owner_set,
+        }
+    }
+
+    /// A game given by the decomposition of its provenance, e.g. a [`factored_product`].
+    pub fn from_decompose(d: RecursiveDecompose<OwnerId>) -> Self {
+        let owner_set = d.all_variables().into();
+        Self {
+            dnf: OnceLock::new(),
+            factored: Some(d),
             owner_set,
         }
     }
 
+    /// The minimized DNF of the provenance.
+    pub fn dnf(&self) -> &Dnf<OwnerId> {
+        self.dnf
+            .get_or_init(|| self.factored.clone().unwrap().expand())
+    }
+
+    pub fn into_dnf(mut self) -> Dnf<OwnerId> {
+        self.dnf();
+        self.dnf.take().unwrap()
+    }
+
+    /// The decomposition of the provenance, decomposing its DNF unless the game was built from
+    /// its decomposition.
+    pub fn decompose(&self) -> RecursiveDecompose<OwnerId> {
+        match &self.factored {
+            Some(d) => d.clone(),
+            None => recursive_decompose(self.dnf(), &self.owner_set),
+        }
+    }
+
+    /// The decomposition the game was built from, if any.
+    pub fn factored(&self) -> Option<&RecursiveDecompose<OwnerId>> {
+        self.factored.as_ref()
+    }
+
     pub fn owner_len(&self) -> usize {
         self.owner_set.len()
     }
 
     pub fn to_syns(&self) -> Vec<&'_ OwnerSet> {
-        dnf_to_syns(&self.dnf)
+        dnf_to_syns(self.dnf())
+    }
+
+    pub fn generate_games(dataset: &DataSet, plan: &JoinPlan) -> Result<Vec<Self>> {
+        let mut games = vec![];
+        Self::generate_join_game_chunks(dataset, plan, usize::MAX, &mut |chunk| {
+            games.extend(chunk);
+            Ok(())
+        })?;
+        Ok(games)
     }
 
-    pub fn generate_games(dataset: &DataSet) -> Result<Vec<Self>> {
+    /// Generate the games of a plan file, which is either a join plan or a union.
+    pub fn generate_games_for(dataset: &DataSet, plan: &Plan) -> Result<Vec<Self>> {
+        match plan {
+            Plan::Join(plan) => Self::generate_games(dataset, plan),
+            Plan::Union(plan) => Self::generate_union_games(dataset, plan),
+        }
+    }
+
+    /// Generate the games of a plan file in chunks of at most `chunk_size` games, in the order of
+    /// [`Game::generate_games_for`], and pass each chunk to `f` as soon as it is created, so that
+    /// the games are never all in memory. Return the number of games.
+    ///
+    /// Games merged by projection or across the branches of a union are created once their join
+    /// is complete, but only their expressions are kept meanwhile, not the join result. Without
+    /// owner assignment, the join is deduplicated as a whole, so it is not run in batches: only
+    /// the games are chunked.
+    pub fn generate_game_chunks(
+        dataset: &DataSet,
+        plan: &Plan,
+        chunk_size: usize,
+        mut f: impl FnMut(Vec<Self>) -> Result<()>,
+    ) -> Result<usize> {
+        ensure!(chunk_size > 0, "chunk size must be positive");
+        let mut num_games = 0;
+        // a boolean query without any result has no game
+        let mut f = |chunk: Vec<Self>| {
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            num_games += chunk.len();
+            f(chunk)
+        };
+        match plan {
+            Plan::Join(plan) => Self::generate_join_game_chunks(dataset, plan, chunk_size, &mut f)?,
+            Plan::Union(plan) => {
+                Self::generate_union_game_chunks(dataset, plan, chunk_size, &mut f)?
+            }
+        }
+        Ok(num_games)
+    }
+
+    /// Generate the games of a union of join plans. The games of the branches with the same
+    /// output key are merged into one game whose provenance is the OR of theirs.
+    pub fn generate_union_games(dataset: &DataSet, plan: &UnionPlan) -> Result<Vec<Self>> {
+        let mut games = vec![];
+        Self::generate_union_game_chunks(dataset, plan, usize::MAX, &mut |chunk| {
+            games.extend(chunk);
+            Ok(())
+        })?;
+        Ok(games)
+    }
+
+    fn generate_union_game_chunks(
+        dataset: &DataSet,
+        plan: &UnionPlan,
+        chunk_size: usize,
+        f: &mut dyn FnMut(Vec<Self>) -> Result<()>,
+    ) -> Result<()> {
+        let mut tuples = OutputTuples::default();
+        for (i, branch) in plan.branches().enumerate() {
+            info!("branch #{i}...");
+            Self::merge_output_tuples(dataset, &branch, chunk_size, &mut tuples)
+                .with_context(|| format!("failed to generate games of branch #{i}"))?;
+        }
+        tuples.into_game_chunks(chunk_size, f)
+    }
+
+    /// Generate the games of a join plan in chunks of at most `chunk_size` games passed to `f`.
+    fn generate_join_game_chunks(
+        dataset: &DataSet,
+        plan: &JoinPlan,
+        chunk_size: usize,
+        f: &mut dyn FnMut(Vec<Self>) -> Result<()>,
+    ) -> Result<()> {
+        if !plan.projection.is_empty() {
+            let mut tuples = OutputTuples::default();
+            Self::merge_output_tuples(dataset, plan, chunk_size, &mut tuples)?;
+            return tuples.into_game_chunks(chunk_size, f);
+        }
         if dataset.owner_set.is_empty() {
-            Self::generate_games_without_assignment(dataset)
+            Self::generate_games_from_owner_columns(dataset, plan, chunk_size, f)
         } else {
-            Self::generate_games_with_assignment(dataset)
+            Self::generate_games_with_assignment(dataset, plan, chunk_size, f)
         }
     }
 
-    fn generate_games_with_assignment(dataset: &DataSet) -> Result<Vec<Self>> {
-        info!("join...");
-        let join_df = join(
-            |table_name| dataset.tables.get(table_name).map(|t| &t.df),
-            PLANS
-                .get(dataset.name.as_str())
-                .context("cannot find join plan")?,
-            true,
-        )?;
-
-        info!("extract row_id_columns...");
-        let row_id_columns: Vec<(String, Vec<RowId>)> = join_df
-            .columns(
-                dataset
-                    .tables
-                    .keys()
-                    .map(|t| format!("{}:{}", ROW_ID_COL_NAME, t)),
-            )?
-            .into_iter()
-            .map(|column| {
-                let table_name = column.name().rsplit(':').next().unwrap().to_string();
-                let row_ids = column
-                    .u64()
-                    .unwrap()
-                    .into_iter()
-                    .map(|row_id| RowId::new(row_id.unwrap()))
-                    .collect();
-                (table_name, row_ids)
-            })
-            .collect();
-        let rows = join_df.shape().0;
-        let cols = row_id_columns.len();
-        drop(join_df);
+    /// Merge the provenance of the output tuples of a join plan into `tuples`, by output key,
+    /// i.e. the projection of the output tuple. All join results of a plan without projection
+    /// have the same, empty key.
+    ///
+    /// With owner assignment, the join runs in batches of `chunk_size` rows of the init table
+    /// (see [`join_batches`]), and every batch is grouped by projection before the next is
+    /// joined.
+    fn merge_output_tuples(
+        dataset: &DataSet,
+        plan: &JoinPlan,
+        chunk_size: usize,
+        tuples: &mut OutputTuples,
+    ) -> Result<()> {
+        if plan.projection.is_empty() {
+            return Self::generate_join_game_chunks(dataset, plan, chunk_size, &mut |games| {
+                for game in games {
+                    let mut exp = game.into_dnf();
+                    exp.minimize();
+                    tuples.merge_one(vec![], exp);
+                }
+                Ok(())
+            });
+        }
+
+        // join results projected to the same output tuple are merged into one game
+        if dataset.owner_set.is_empty() {
+            let (join_df, owner_columns) = owner_column_join(dataset, plan)?;
+            info!("group by projection...");
+            let (keys, groups) = group_rows(&join_df, &plan.projection)?;
+            let exps = groups
+                .par_iter()
+                .map(|group| {
+                    let mut exp: Dnf<OwnerId> = group
+                        .iter()
+                        .map(|&i| row_implicant(&owner_columns, i))
+                        .collect();
+                    exp.minimize();
+                    exp
+                })
+                .collect();
+            tuples.merge(&keys, exps);
+            return Ok(());
+        }
+
+        info!("join and group by projection...");
+        let df_fn = |table_name: &str| dataset.tables.get(table_name).map(|t| &t.df);
+        join_batches(df_fn, plan, chunk_size, |batch| {
+            let (keys, groups) = group_rows(&batch, &plan.projection)?;
+            let row_exp = row_exp_fn(dataset, plan, &batch)?;
+            let exps = groups
+                .par_iter()
+                .map(|group| {
+                    let mut exp = Dnf::new();
+                    for &i in group {
+                        exp.extend(row_exp(i));
+                    }
+                    exp.minimize();
+                    exp
+                })
+                .collect();
+            tuples.merge(&keys, exps);
+            Ok(())
+        })
+    }
+
+    /// Generate the games of a plan without projection, one per join result, or a single one
+    /// for a boolean query. The join runs in batches of `chunk_size` rows of the init table
+    /// (see [`join_batches`]), and the games of every batch are created before the next batch is
+    /// joined.
+    fn generate_games_with_assignment(
+        dataset: &DataSet,
+        plan: &JoinPlan,
+        chunk_size: usize,
+        f: &mut dyn FnMut(Vec<Self>) -> Result<()>,
+    ) -> Result<()> {
+        let df_fn = |table_name: &str| dataset.tables.get(table_name).map(|t| &t.df);
+
+        info!("join and create games...");
+        let mut boolean_exp = Dnf::new();
+        join_batches(df_fn, plan, chunk_size, |batch| {
+            let rows = batch.height();
+            if plan.boolean {
+                let row_exp = row_exp_fn(dataset, plan, &batch)?;
+                boolean_exp.extend_minimal(Self::boolean_exp(
+                    (0..rows).into_par_iter().flat_map_iter(row_exp),
+                ));
+                return Ok(());
+            }
+
+            // the provenance of a single join result is the AND of the ORs of the owners of its
+            // rows, which is built factored instead of expanding the product
+            let row_owners = row_owners_fn(dataset, plan, &batch)?;
+            for start in (0..rows).step_by(chunk_size) {
+                let end = rows.min(start.saturating_add(chunk_size));
+                f((start..end)
+                    .into_par_iter()
+                    .map(|i| Self::from_decompose(factored_product(&row_owners(i))))
+                    .collect())?;
+            }
+            Ok(())
+        })?;
+        if plan.boolean {
+            f(Self::boolean_game(boolean_exp))?;
+        }
+        Ok(())
+    }
+
+    /// Generate the games of a plan without projection for datasets whose owners are identified
+    /// by the `owner_columns` of the plan: every join result is a game whose provenance is the
+    /// AND of its owners, or the OR of all of them for a boolean query.
+    fn generate_games_from_owner_columns(
+        dataset: &DataSet,
+        plan: &JoinPlan,
+        chunk_size: usize,
+        f: &mut dyn FnMut(Vec<Self>) -> Result<()>,
+    ) -> Result<()> {
+        let (join_df, owner_columns) = owner_column_join(dataset, plan)?;
+        let rows = join_df.height();
+        if plan.boolean {
+            info!("create game...");
+            let implicants = (0..rows)
+                .into_par_iter()
+                .map(|i| row_implicant(&owner_columns, i));
+            return f(Self::boolean_game(Self::boolean_exp(implicants)));
+        }
 
         info!("create games...");
-        let row_id_columns_ref = &row_id_columns;
-        let games: Vec<_> = (0..rows)
-            .into_par_iter()
-            .map(move |i| {
-                let owner_sets = (0..cols)
-                    .map(move |j| {
-                        let (table_name, row_ids) = &row_id_columns_ref[j];
-                        let row_id = row_ids[i];
-                        let owner_set = &dataset.tables[table_name].owner_map[&row_id];
-                        owner_set.clone()
-                    })
-                    .collect::<Vec<OwnerSet>>();
+        for start in (0..rows).step_by(chunk_size) {
+            let end = rows.min(start.saturating_add(chunk_size));
+            f((start..end)
+                .into_par_iter()
+                .map(|i| {
+                    let row_series = owner_columns
+                        .iter()
+                        .map(|column| column.slice(i as i64, 1))
+                        .collect::<Vec<_>>();
+                    Self::generate_games_with_agg_helper(&row_series)
+                })
+                .collect::<Result<_>>()?)?;
+        }
+        Ok(())
+    }
 
-                let owner_sets_per_tuple = cartesian_product(&owner_sets);
+    /// The OR of `implicants`, the provenance of a boolean query.
+    ///
+    /// The DNF is minimized as it grows, so the provenance of individual join results is never
+    /// materialized as a whole.
+    fn boolean_exp(implicants: impl ParallelIterator<Item = Implicant<OwnerId>>) -> Dnf<OwnerId> {
+        implicants
+            .fold(Dnf::new, |mut exp, term| {
+                exp.insert_minimal(term);
+                exp
+            })
+            .reduce(Dnf::new, |mut exp, other| {
+                exp.extend_minimal(other);
+                exp
+            })
+    }
 
-                let mut exp_set: BTreeSet<Implicant<OwnerId>> = BTreeSet::default();
-                for owner_set in owner_sets_per_tuple {
-                    let set = Implicant::from_iter(owner_set);
-                    exp_set.insert(set);
-                }
+    /// The single game of a boolean query whose provenance is `exp`. A query without any result
+    /// has no game.
+    fn boolean_game(exp: Dnf<OwnerId>) -> Vec<Self> {
+        if exp.is_false() {
+            vec![]
+        } else {
+            vec![Self::new(exp)]
+        }
+    }
 
-                let mut exp = Dnf::from(exp_set);
-                exp.minimize();
+    pub fn generate_games_with_agg_helper(row_series: &[Series]) -> Result<Self> {
+        let rows = row_series[0].len(); // Assuming all RowSeries have the same length
 
-                let owner_set = exp.all_variables().into();
-                Self {
-                    dnf: exp,
-                    owner_set,
-                }
+        let mut exp_set = BTreeSet::new();
+        exp_set.par_extend(
+            (0..rows)
+                .into_par_iter()
+                .map(|i| row_implicant(row_series, i)),
+        );
+
+        let mut exp = Dnf::from(exp_set);
+        exp.minimize();
+
+        Ok(Self::new(exp))
+    }
+}
+
+/// The owners of the i-th row, one per column of `row_series`, whose owner ids have been
+/// converted by [`owner_id_column`]. Nulls are skipped.
+fn row_implicant(row_series: &[Series], i: usize) -> Implicant<OwnerId> {
+    row_series
+        .iter()
+        .filter_map(|column| match column.get(i) {
+            AnyValue::UInt32(owner_id) => Some(OwnerId::from(owner_id)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Convert the integer owner ids of an owner column to `u32`, failing on ids out of its range
+/// rather than truncating them, which would merge distinct owners. Other columns are kept as
+/// they are, and contribute no owner.
+fn owner_id_column(column: &Series) -> Result<Series> {
+    fn convert<T: Copy + std::fmt::Display>(
+        column: &Series,
+        values: impl Iterator<Item = Option<T>>,
+    ) -> Result<Series>
+    where
+        u32: TryFrom<T>,
+    {
+        let owner_ids = values
+            .map(|value| {
+                value
+                    .map(|value| {
+                        u32::try_from(value).ok().with_context(|| {
+                            format!(
+                                "owner id {value} of column {} is out of range",
+                                column.name()
+                            )
+                        })
+                    })
+                    .transpose()
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Series::new(column.name(), owner_ids))
+    }
 
-        drop(row_id_columns);
+    match column.dtype() {
+        DataType::UInt64 => convert(column, column.u64()?.into_iter()),
+        DataType::Int32 => convert(column, column.i32()?.into_iter()),
+        DataType::Int64 => convert(column, column.i64()?.into_iter()),
+        _ => Ok(column.clone()),
+    }
+}
 
-        Ok(games)
+/// The owners of the rows of the i-th join result of `join_df`, one set per table. A table
+/// missing from the result of an outer join (a null row id) does not contribute to its
+/// provenance: the result exists as long as the rows it does have exist.
+///
+/// Fail if a row of the join has no owner, since its join results would have no provenance.
+fn row_owners_fn<'a>(
+    dataset: &'a DataSet,
+    plan: &JoinPlan,
+    join_df: &DataFrame,
+) -> Result<impl Fn(usize) -> Vec<&'a [OwnerId]> + Sync> {
+    // the row id columns are read in place rather than copied
+    let row_id_columns: Vec<(&Table, UInt64Chunked)> = plan
+        .tables()
+        .map(|t| {
+            let column = join_df.column(&format!("{}:{}", ROW_ID_COL_NAME, t))?;
+            Ok((&dataset.tables[t], column.u64()?.rechunk()))
+        })
+        .collect::<Result<_>>()?;
+    for (table, row_ids) in &row_id_columns {
+        if let Some(row_id) = row_ids
+            .into_iter()
+            .flatten()
+            .find(|&row_id| table.owner_map.get(RowId(row_id)).is_empty())
+        {
+            bail!(
+                "table {}: row id {row_id} has no owner, see `validate`",
+                table.name
+            );
+        }
     }
 
-    fn generate_games_without_assignment(dataset: &DataSet) -> Result<Vec<Self>> {
-        let df = Self::join_df(dataset)?;
-        let rows = df.height();
-        let cols = df.width();
-        let games: Vec<Self> = (0..rows)
-            .into_par_iter()
-            .map(|row_idx| {
-                // Create a vector to store the Series for the current row
-                let mut row_series: Vec<Series> = Vec::with_capacity(cols);
+    Ok(move |i: usize| {
+        row_id_columns
+            .iter()
+            .filter_map(|(table, row_ids)| Some(table.owner_map.get(RowId::new(row_ids.get(i)?))))
+            .collect()
+    })
+}
 
-                // Iterate through each column
-                for col_idx in 0..cols {
-                    // Select the column by index
-                    let cell = df.select_at_idx(col_idx).unwrap().get(row_idx);
+/// The provenance of the i-th join result of `join_df`, not minimized.
+fn row_exp_fn<'a>(
+    dataset: &'a DataSet,
+    plan: &JoinPlan,
+    join_df: &DataFrame,
+) -> Result<impl Fn(usize) -> Dnf<OwnerId> + Sync + 'a> {
+    let row_owners = row_owners_fn(dataset, plan, join_df)?;
+    Ok(move |i: usize| {
+        let owner_sets_per_tuple = cartesian_product(&row_owners(i));
+
+        let mut exp_set: BTreeSet<Implicant<OwnerId>> = BTreeSet::default();
+        for owner_set in owner_sets_per_tuple {
+            let set = Implicant::from_iter(owner_set);
+            exp_set.insert(set);
+        }
+        Dnf::from(exp_set)
+    })
+}
 
-                    let owner_list = match cell {
-                        AnyValue::List(rows) => Ok(rows),
-                        _ => Err(Error::msg("unexpected col in the aggregated df")),
-                    };
+/// Group the row indices of `df` by the values of columns `by`, in the order of first occurrence.
+/// Return the values of `by` of each group as well.
+fn group_rows(df: &DataFrame, by: &[String]) -> Result<(DataFrame, Vec<Vec<usize>>)> {
+    let groups: Vec<Vec<usize>> = df
+        .groupby_stable(by)?
+        .get_groups()
+        .iter()
+        .map(|group| match group {
+            GroupsIndicator::Idx((_, rows)) => rows.iter().map(|&i| i as usize).collect(),
+            GroupsIndicator::Slice([first, len]) => {
+                (first as usize..(first + len) as usize).collect()
+            }
+        })
+        .collect();
+    let first_rows = groups.iter().map(|group| group[0] as IdxSize).collect();
+    let keys = df.select(by)?.take(&IdxCa::from_vec("", first_rows))?;
+    Ok((keys, groups))
+}
 
-                    row_series.push(owner_list.unwrap());
-                }
-                Self::generate_games_with_agg_helper(&row_series).unwrap()
+/// The join result of a plan for a dataset without owner assignment, and its owner columns.
+fn owner_column_join(dataset: &DataSet, plan: &JoinPlan) -> Result<(DataFrame, Vec<Series>)> {
+    ensure!(
+        !plan.owner_columns.is_empty(),
+        "dataset {} has no owner assignment and the join plan declares no owner columns",
+        dataset.name
+    );
+
+    info!("join...");
+    let join_df = join(
+        |table_name| dataset.tables.get(table_name).map(|t| &t.df),
+        plan,
+        false,
+    )?;
+    let owner_columns = join_df
+        .select_series(plan.match_owner_columns(join_df.get_column_names())?)?
+        .iter()
+        .map(|column| {
+            owner_id_column(column).with_context(|| match column_table(dataset, plan, column) {
+                Some(table) => format!("table {table}: invalid owner column {}", column.name()),
+                None => format!("invalid owner column {}", column.name()),
             })
-            .collect();
-        Ok(games)
+        })
+        .collect::<Result<_>>()?;
+    Ok((join_df, owner_columns))
+}
+
+/// The table of the plan a column of its join result comes from: the table of its suffix
+/// `<column>:<table>`, or else the first table having it. Derived columns have no table.
+fn column_table<'a>(dataset: &DataSet, plan: &'a JoinPlan, column: &Series) -> Option<&'a str> {
+    let has_column = |table: &str, name: &str| {
+        dataset
+            .tables
+            .get(table)
+            .is_some_and(|t| t.df.column(name).is_ok())
+    };
+    if let Some((name, table)) = column.name().rsplit_once(':') {
+        if let Some(table) = plan.tables().find(|&t| t == table && has_column(t, name)) {
+            return Some(table);
+        }
     }
+    plan.tables().find(|&t| has_column(t, column.name()))
+}
 
-    fn join_df(dataset: &DataSet) -> Result<DataFrame> {
-        info!("join...");
-        let mut join_df = join(
-            |table_name| dataset.tables.get(table_name).map(|t| &t.df),
-            PLANS
-                .get(dataset.name.as_str())
-                .context("cannot find join plan")?,
-            false,
-        )?;
+/// The provenance of output tuples, merged by output key across join batches and union
+/// branches, in the order of their first join result.
+#[derive(Debug, Default)]
+struct OutputTuples {
+    index: HashMap<Vec<String>, usize>,
+    exps: Vec<Dnf<OwnerId>>,
+}
 
-        Self::win(&mut join_df);
+impl OutputTuples {
+    /// OR the minimized provenance `exps` of the output tuples keyed by the rows of `keys` into
+    /// theirs.
+    fn merge(&mut self, keys: &DataFrame, exps: Vec<Dnf<OwnerId>>) {
+        for (key, exp) in key_rows(keys).into_iter().zip(exps) {
+            self.merge_one(key, exp);
+        }
+    }
 
-        let mask_season = join_df
-            .column("season")
-            .expect("season:2015/2016")
-            .equal("2015/2016")
-            .unwrap();
-        let mask_winner = join_df
-            .column("winner")
-            .expect("Not a draw!")
-            .not_equal("DRAW")
-            .unwrap();
-        let combined_mask = mask_season & mask_winner;
-
-        join_df = join_df.filter(&combined_mask).unwrap();
-
-        let cols = join_df
-            .get_column_names()
-            .into_par_iter()
-            .filter_map(|col| {
-                if col.contains("api_id") {
-                    Some(col.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<String>>();
-        join_df = join_df
-            .groupby(["winner"])
-            .unwrap()
-            .select(cols)
-            .agg_list()
-            .unwrap();
-        join_df = join_df.sort(["winner"], false)?;
-        join_df = join_df.drop("winner")?;
-        Ok(join_df)
+    fn merge_one(&mut self, key: Vec<String>, exp: Dnf<OwnerId>) {
+        match self.index.entry(key) {
+            Entry::Occupied(entry) => self.exps[*entry.get()].extend_minimal(exp),
+            Entry::Vacant(entry) => {
+                entry.insert(self.exps.len());
+                self.exps.push(exp);
+            }
+        }
     }
 
-    fn win(df: &mut DataFrame) {
-        let home_score = df.column("home_team_goal").unwrap().i64().unwrap();
-        let away_score = df.column("away_team_goal").unwrap().i64().unwrap();
+    /// Create the games of the output tuples in chunks of at most `chunk_size` games passed to
+    /// `f`.
+    fn into_game_chunks(
+        self,
+        chunk_size: usize,
+        f: &mut dyn FnMut(Vec<Game>) -> Result<()>,
+    ) -> Result<()> {
+        let mut exps = self.exps.into_iter();
+        loop {
+            let chunk: Vec<Dnf<OwnerId>> = exps.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            f(chunk.into_par_iter().map(Game::new).collect())?;
+        }
+    }
+}
 
-        //TODO: get the team names
-        let home_team_name = df.column("team_long_name").unwrap().utf8().unwrap();
-        let away_team_name = df
-            .column("team_long_name:AwayTeam")
-            .unwrap()
-            .utf8()
-            .unwrap();
-        let draw = "DRAW";
+/// The values of each row of `df`, rendered as strings so that they can be compared across
+/// data frames.
+fn key_rows(df: &DataFrame) -> Vec<Vec<String>> {
+    (0..df.height())
+        .map(|i| {
+            df.get_columns()
+                .iter()
+                .map(|column| column.get(i).to_string())
+                .collect()
+        })
+        .collect()
+}
 
-        let winner = home_score
-            .into_iter()
-            .zip(away_score.into_iter())
-            .zip(home_team_name.into_iter())
-            .zip(away_team_name.into_iter())
-            .map(
-                |(((home_score, away_score), home_team_name), away_team_name)| {
-                    if home_score > away_score {
-                        home_team_name.unwrap()
-                    } else if home_score < away_score {
-                        away_team_name.unwrap()
-                    } else {
-                        draw
-                    }
-                },
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{fixture_dataset, fixture_soccer_dataset, game_dnfs, sorted_dnfs},
+        CmpOp, Operand, Predicate, PLANS,
+    };
+    use polars_core::prelude::JoinType;
+
+    #[test]
+    fn test_generate_games_with_projection() {
+        let dataset = fixture_dataset();
+        let builder = || {
+            JoinPlan::builder().init_table("customer").join(
+                "orders",
+                ["c_custkey"],
+                ["o_custkey"],
+                JoinType::Inner,
             )
-            .collect::<Vec<_>>();
+        };
 
-        let _ = df.with_column(Series::new("winner", winner));
+        let plan = builder().build_for(&dataset).unwrap();
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1 3", "1 3 + 1 4", "2 5"]);
+
+        let plan = builder().project(["o_status"]).build_for(&dataset).unwrap();
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1 3 + 1 4", "1 3 + 2 5"]);
+
+        let plan = builder().project(["c_name"]).build_for(&dataset).unwrap();
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1 3 + 1 4", "2 5"]);
     }
 
-    pub fn generate_games_with_agg_helper(row_series: &[Series]) -> Result<Self> {
-        let rows = row_series[0].len(); // Assuming all RowSeries have the same length
-        let cols = row_series.len();
-
-        let owner_sets: Vec<OwnerSet> = (0..rows)
-            .into_par_iter()
-            .map(|i| {
-                let owner_set = (0..cols)
-                    .filter_map(|j| match row_series[j].get(i) {
-                        AnyValue::UInt32(owner_id) => Some(OwnerId::from(owner_id)),
-                        AnyValue::UInt64(owner_id) => Some(OwnerId::from(owner_id as u32)),
-                        AnyValue::Int32(owner_id) => Some(OwnerId::from(owner_id as u32)),
-                        AnyValue::Int64(owner_id) => Some(OwnerId::from(owner_id as u32)),
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>();
-                OwnerSet::from_iter(owner_set)
-            })
+    #[test]
+    fn test_generate_factored_games() {
+        let mut dataset = fixture_dataset();
+        // alice shares the second order, bob's row has two owners
+        let orders = dataset.tables.get_mut("orders").unwrap();
+        orders.owner_map = [(0, 3), (1, 1), (1, 4), (2, 5), (3, 7)]
+            .into_iter()
+            .map(|(row_id, owner)| (RowId(row_id), OwnerId(owner)))
+            .collect();
+        let customer = dataset.tables.get_mut("customer").unwrap();
+        customer.owner_map = [(0, 1), (1, 2), (1, 8), (2, 6)]
+            .into_iter()
+            .map(|(row_id, owner)| (RowId(row_id), OwnerId(owner)))
             .collect();
 
-        let mut exp_set = BTreeSet::new();
-        exp_set.extend(owner_sets.into_iter().map(Implicant::from_iter));
+        let plan = JoinPlan::builder()
+            .init_table("customer")
+            .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Inner)
+            .build_for(&dataset)
+            .unwrap();
+        let games = Game::generate_games(&dataset, &plan).unwrap();
+        for game in &games {
+            let dnf = game.dnf().clone();
+            assert_eq!(
+                game.decompose(),
+                recursive_decompose(&dnf, &dnf.all_variables())
+            );
+            assert_eq!(game.owner_set, dnf.all_variables().into());
+        }
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1", "1 3", "2 5 + 5 8"]);
+
+        // a joined row without owner is an error rather than a panic
+        let orders = dataset.tables.get_mut("orders").unwrap();
+        orders.owner_map = orders
+            .owner_map
+            .pairs()
+            .filter(|(row_id, _)| row_id.0 != 2)
+            .collect();
+        let err = Game::generate_games(&dataset, &plan).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "table orders: row id 2 has no owner, see `validate`"
+        );
+    }
 
-        let mut exp = Dnf::from(exp_set);
-        exp.minimize();
+    #[test]
+    fn test_generate_game_chunks() {
+        let dataset = fixture_dataset();
+        let builder = |join_type| {
+            JoinPlan::builder().init_table("customer").join(
+                "orders",
+                ["c_custkey"],
+                ["o_custkey"],
+                join_type,
+            )
+        };
+        let outer = || builder(JoinType::Outer);
+        let mut boolean = outer().build().unwrap();
+        boolean.boolean = true;
+        let union = UnionPlan {
+            branches: vec![outer().build().unwrap(), outer().build().unwrap()],
+            output_key: vec!["o_orderkey".to_string()],
+        };
+        // inner joins run in batches, whose output tuples are merged
+        let inner_union = UnionPlan {
+            branches: vec![
+                builder(JoinType::Inner).build().unwrap(),
+                JoinPlan::builder().init_table("orders").build().unwrap(),
+            ],
+            output_key: vec!["o_status".to_string()],
+        };
+        let plans = [
+            (&dataset, Plan::Join(outer().build().unwrap())),
+            (
+                &dataset,
+                Plan::Join(outer().project(["o_status"]).build().unwrap()),
+            ),
+            (
+                &dataset,
+                Plan::Join(
+                    builder(JoinType::Inner)
+                        .project(["o_status"])
+                        .build()
+                        .unwrap(),
+                ),
+            ),
+            (&dataset, Plan::Join(boolean)),
+            (&dataset, Plan::Union(union)),
+            (&dataset, Plan::Union(inner_union)),
+        ];
+        let soccer = fixture_soccer_dataset();
+        let mut soccer_plan = PLANS["soccer"].clone();
+        soccer_plan.projection.clear();
+
+        for (dataset, plan) in plans
+            .into_iter()
+            .chain([(&soccer, Plan::Join(PLANS["soccer"].clone()))])
+            .chain([(&soccer, Plan::Join(soccer_plan))])
+        {
+            let expected: Vec<String> = Game::generate_games_for(dataset, &plan)
+                .unwrap()
+                .iter()
+                .map(|g| g.dnf().to_string())
+                .collect();
+            for chunk_size in [1, 2, 100] {
+                let mut actual = vec![];
+                let num_games = Game::generate_game_chunks(dataset, &plan, chunk_size, |chunk| {
+                    assert!(!chunk.is_empty() && chunk.len() <= chunk_size);
+                    actual.extend(chunk.iter().map(|g| g.dnf().to_string()));
+                    Ok(())
+                })
+                .unwrap();
+                assert_eq!(actual, expected);
+                assert_eq!(num_games, expected.len());
+            }
+        }
+        let plan = Plan::Join(PLANS["soccer"].clone());
+        assert!(Game::generate_game_chunks(&soccer, &plan, 0, |_| Ok(())).is_err());
+    }
 
-        let owner_set = exp.all_variables().into();
-        let game = Self {
-            dnf: exp,
-            owner_set,
+    #[test]
+    fn test_generate_games_without_assignment() {
+        let dataset = fixture_soccer_dataset();
+        let plan = &PLANS["soccer"];
+        assert_eq!(game_dnfs(&dataset, plan), vec!["10 20", "20 30"]);
+
+        // the same pipeline with another season
+        let mut plan = plan.clone();
+        plan.filters[0] = Predicate::new("season", CmpOp::Eq, Operand::Str("2014/2015".into()));
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["10 30"]);
+
+        // one game per match, owned by the home team only
+        let mut plan = PLANS["soccer"].clone();
+        plan.projection.clear();
+        plan.owner_columns = vec!["home_team_api_id".to_string()];
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["10", "20"]);
+
+        // owner ids out of the range of u32 are not truncated into other owners
+        for owner_id in [(1i64 << 32) + 10, -1] {
+            let mut dataset = fixture_soccer_dataset();
+            for (table, column, ids) in [
+                (
+                    "Match",
+                    "home_team_api_id",
+                    vec![owner_id, 20, owner_id, 30],
+                ),
+                ("HomeTeam", "team_api_id", vec![owner_id, 20, 30]),
+            ] {
+                let df = &mut dataset.tables.get_mut(table).unwrap().df;
+                df.with_column(Series::new(column, ids)).unwrap();
+            }
+            let err = Game::generate_games(&dataset, &plan).unwrap_err();
+            assert_eq!(
+                format!("{err:#}"),
+                format!(
+                    "table Match: invalid owner column home_team_api_id: owner id {owner_id} of \
+                     column home_team_api_id is out of range"
+                )
+            );
+        }
+
+        plan.owner_columns = vec!["player_api_id".to_string()];
+        assert!(Game::generate_games(&dataset, &plan).is_err());
+        plan.owner_columns.clear();
+        assert!(Game::generate_games(&dataset, &plan).is_err());
+    }
+
+    #[test]
+    fn test_generate_games_with_outer_join() {
+        let dataset = fixture_dataset();
+        let plan = |join_type| {
+            JoinPlan::builder()
+                .init_table("customer")
+                .join("orders", ["c_custkey"], ["o_custkey"], join_type)
+                .build_for(&dataset)
+                .unwrap()
         };
 
-        Ok(game)
+        // carol has no order
+        assert_eq!(
+            game_dnfs(&dataset, &plan(JoinType::Left)),
+            vec!["1 3", "1 3 + 1 4", "2 5", "6"]
+        );
+        // nor does order 13 have a customer
+        assert_eq!(
+            game_dnfs(&dataset, &plan(JoinType::Outer)),
+            vec!["1 3", "1 3 + 1 4", "2 5", "6", "7"]
+        );
+
+        let plan = JoinPlan::builder()
+            .init_table("customer")
+            .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Outer)
+            .project(["o_status"])
+            .build_for(&dataset)
+            .unwrap();
+        assert_eq!(
+            game_dnfs(&dataset, &plan),
+            vec!["1 3 + 2 5", "6", "7 + 1 3 + 1 4"]
+        );
     }
-}
 
-/// A boolean expression
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(untagged)]
-enum BoolExp<T> {
-    And { and: Vec<BoolExp<T>> },
-    Or { or: Vec<BoolExp<T>> },
-    Elm(T),
-}
+    #[test]
+    fn test_generate_union_games() {
+        let dataset = fixture_dataset();
+        let orders_with_status = |status: &str| {
+            JoinPlan::builder()
+                .init_table("customer")
+                .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Inner)
+                .filter(Predicate::new(
+                    "o_status",
+                    CmpOp::Eq,
+                    Operand::Str(status.into()),
+                ))
+                .build()
+                .unwrap()
+        };
+        let union_dnfs = |plan: &UnionPlan| {
+            plan.validate_schema(&dataset).unwrap();
+            sorted_dnfs(&Game::generate_union_games(&dataset, plan).unwrap())
+        };
+
+        let mut plan = UnionPlan {
+            branches: vec![orders_with_status("F"), orders_with_status("O")],
+            output_key: vec!["c_name".to_string()],
+        };
+        assert_eq!(union_dnfs(&plan), vec!["1 3 + 1 4", "2 5"]);
+
+        // absorbed by a branch with smaller provenance
+        plan.branches
+            .push(JoinPlan::builder().init_table("customer").build().unwrap());
+        assert_eq!(union_dnfs(&plan), vec!["1", "2", "6"]);
 
-impl<T> FromStr for BoolExp<T>
-where
-    T: for<'de> Deserialize<'de>,
-{
-    type Err = Error;
+        plan.output_key.clear();
+        assert_eq!(union_dnfs(&plan), vec!["1 + 2 + 6"]);
+    }
+
+    #[test]
+    fn test_generate_boolean_game() {
+        let dataset = fixture_dataset();
+        let plan = JoinPlan::builder()
+            .init_table("customer")
+            .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Inner)
+            .boolean(true)
+            .build_for(&dataset)
+            .unwrap();
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1 3 + 1 4 + 2 5"]);
+
+        let plan = JoinPlan::builder()
+            .init_table("orders")
+            .filter(Predicate::new(
+                "o_status",
+                CmpOp::Eq,
+                Operand::Str("X".into()),
+            ))
+            .boolean(true)
+            .build_for(&dataset)
+            .unwrap();
+        assert!(game_dnfs(&dataset, &plan).is_empty());
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(serde_json::from_str(s)?)
+        let dataset = fixture_soccer_dataset();
+        let mut plan = PLANS["soccer"].clone();
+        plan.projection.clear();
+        plan.boolean = true;
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["10 20 + 20 30"]);
     }
 }
@@ -0,0 +1,308 @@
+//! Game files: the games generated from a dataset and a plan, stored so that Shapley values can
+//! be computed again, e.g. by another method, without loading the dataset.
+//!
+//! A game file is JSON lines: a [`GameFileHeader`], then the minimized DNF of every game as the
+//! list of its implicants, each a list of owner ids, e.g. `[[1,3],[1,4]]`.
+//!
+//! Hand-crafted games are read by [`read_exps`] from a file of boolean expressions instead, and
+//! games are exchanged with external tools as DIMACS-like files, see [`Dnf::read_dimacs`].
+
+use crate::{
+    dnf::{BoolExp, Implicant},
+    Dnf, Game, OwnerId, OwnerNames, Plan,
+};
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// What a game file was generated from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameFileHeader {
+    pub dataset: String,
+    pub plan: Plan,
+    pub query: Option<String>,
+    pub boolean_query: bool,
+    /// The names of the owners of the dataset, see [`OwnerNames`].
+    pub owner_names: Vec<String>,
+    pub num_games: usize,
+}
+
+impl GameFileHeader {
+    pub fn owner_names(&self) -> OwnerNames {
+        self.owner_names.iter().collect()
+    }
+}
+
+/// Write `games` to a game file at `path`. `num_games` of the header is set to their number.
+pub fn write_games(path: impl AsRef<Path>, header: &GameFileHeader, games: &[Game]) -> Result<()> {
+    let path = path.as_ref();
+    let f = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut w = BufWriter::new(f);
+    let header = GameFileHeader {
+        num_games: games.len(),
+        ..header.clone()
+    };
+    serde_json::to_writer(&mut w, &header)?;
+    writeln!(w)?;
+    for game in games {
+        let implicants: Vec<Vec<u32>> = game
+            .dnf()
+            .iter()
+            .map(|implicant| implicant.iter().map(|owner| owner.0).collect())
+            .collect();
+        serde_json::to_writer(&mut w, &implicants)?;
+        writeln!(w)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Read a game file written by [`write_games`].
+pub fn read_games(path: impl AsRef<Path>) -> Result<(GameFileHeader, Vec<Game>)> {
+    let path = path.as_ref();
+    let f = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut lines = BufReader::new(f).lines();
+    let header: GameFileHeader = serde_json::from_str(
+        &lines
+            .next()
+            .with_context(|| format!("{} is empty", path.display()))??,
+    )
+    .with_context(|| format!("invalid header of {}", path.display()))?;
+
+    let mut games = Vec::with_capacity(header.num_games);
+    for (i, line) in lines.enumerate() {
+        let implicants: Vec<Vec<u32>> = serde_json::from_str(&line?)
+            .with_context(|| format!("invalid game #{i} in {}", path.display()))?;
+        let mut dnf: Dnf<OwnerId> = implicants
+            .into_iter()
+            .map(|implicant| implicant.into_iter().map(OwnerId).collect::<Implicant<_>>())
+            .collect();
+        dnf.minimize();
+        ensure!(
+            !dnf.is_true() && !dnf.is_false(),
+            "game #{i} in {} has a constant expression {dnf}",
+            path.display()
+        );
+        games.push(Game::new(dnf));
+    }
+    ensure!(
+        games.len() == header.num_games,
+        "{} has {} games but its header declares {}",
+        path.display(),
+        games.len(),
+        header.num_games
+    );
+    Ok((header, games))
+}
+
+/// Parse a game from a boolean expression over owner ids: either a DNF in its display format,
+/// e.g. `1 2 + 3 4`, or a nested [`BoolExp`] in JSON, e.g. `{"and": [1, {"or": [2, 3]}]}`.
+pub fn parse_exp(s: &str) -> Result<Game> {
+    let s = s.trim();
+    let mut dnf = if s.starts_with('{') {
+        let exp = s.parse::<BoolExp<OwnerId>>()?;
+        // a read-once expression is its own decomposition, so it is not expanded to DNF
+        if exp.is_read_once() {
+            let d = exp
+                .to_decompose()
+                .with_context(|| format!("constant expression {s}"))?;
+            return Ok(Game::from_decompose(d));
+        }
+        exp.to_dnf()
+    } else {
+        s.parse::<Dnf<OwnerId>>()?
+    };
+    dnf.minimize();
+    ensure!(
+        !dnf.is_true() && !dnf.is_false(),
+        "constant expression {dnf}"
+    );
+    Ok(Game::new(dnf))
+}
+
+/// Read hand-crafted games from a file of boolean expressions, one per line as in
+/// [`parse_exp`]. Empty lines and lines starting with `#` are skipped.
+pub fn read_exps(path: impl AsRef<Path>) -> Result<Vec<Game>> {
+    let path = path.as_ref();
+    let f = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut games = vec![];
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let game =
+            parse_exp(&line).with_context(|| format!("line {}: {}", i + 1, path.display()))?;
+        games.push(game);
+    }
+    Ok(games)
+}
+
+/// Read a game from each of the DIMACS-like files at `paths`. The variables are owner names,
+/// interned into `owner_names`.
+pub fn read_dimacs_games(paths: &[PathBuf], owner_names: &mut OwnerNames) -> Result<Vec<Game>> {
+    paths
+        .iter()
+        .map(|path| {
+            let f =
+                File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+            let mut dnf = Dnf::<String>::read_dimacs(BufReader::new(f))
+                .with_context(|| format!("invalid DIMACS file {}", path.display()))?;
+            dnf.minimize();
+            ensure!(
+                !dnf.is_true() && !dnf.is_false(),
+                "{} has a constant expression {dnf}",
+                path.display()
+            );
+            for name in dnf.all_variables() {
+                owner_names.intern(&name);
+            }
+            Ok(Game::new(
+                dnf.map_variable(|name| owner_names.id(name).unwrap()),
+            ))
+        })
+        .collect()
+}
+
+/// Write every game to `<dir>/game-<i>.dnf` in the DIMACS-like format, by owner name.
+pub fn write_dimacs_games(
+    dir: impl AsRef<Path>,
+    games: &[Game],
+    owner_names: &OwnerNames,
+) -> Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    for (i, game) in games.iter().enumerate() {
+        let path = dir.join(format!("game-{i}.dnf"));
+        let f =
+            File::create(&path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut w = BufWriter::new(f);
+        game.dnf()
+            .map_variable(|&owner| owner_names.display(owner))
+            .write_dimacs(&mut w)?;
+        w.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::fixture_dataset, JoinPlan};
+    use polars::prelude::JoinType;
+    use std::fs;
+
+    #[test]
+    fn test_game_file() {
+        let dataset = fixture_dataset();
+        let plan = JoinPlan::builder()
+            .init_table("customer")
+            .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Outer)
+            .project(["o_status"])
+            .build()
+            .unwrap();
+        let games = Game::generate_games(&dataset, &plan).unwrap();
+        let header = GameFileHeader {
+            dataset: dataset.name.clone(),
+            plan: Plan::Join(plan),
+            query: None,
+            boolean_query: false,
+            owner_names: vec![],
+            num_games: 0,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("games.jsonl");
+        write_games(&path, &header, &games).unwrap();
+        let (actual_header, actual) = read_games(&path).unwrap();
+        assert_eq!(actual_header.num_games, games.len());
+        assert!(matches!(actual_header.plan, Plan::Join(plan) if plan.projection == ["o_status"]));
+        assert_eq!(
+            actual.iter().map(|g| g.dnf()).collect::<Vec<_>>(),
+            games.iter().map(|g| g.dnf()).collect::<Vec<_>>()
+        );
+        assert_eq!(actual[0].owner_set, games[0].owner_set);
+
+        // a truncated file is rejected
+        let content = fs::read_to_string(&path).unwrap();
+        let truncated: Vec<&str> = content.lines().take(games.len()).collect();
+        fs::write(&path, truncated.join("\n")).unwrap();
+        let err = read_games(&path).unwrap_err();
+        assert!(err.to_string().contains("its header declares"));
+
+        // games are minimized, and constant games are rejected
+        let header_line = content.lines().next().unwrap();
+        let one_game = header_line.replacen(
+            &format!("\"num_games\":{}", games.len()),
+            "\"num_games\":1",
+            1,
+        );
+        fs::write(&path, format!("{one_game}\n[[1, 2], [1], [3]]\n")).unwrap();
+        let (_, actual) = read_games(&path).unwrap();
+        assert_eq!(actual[0].dnf().to_string(), "1 + 3");
+        for constant in ["[]", "[[1], []]"] {
+            fs::write(&path, format!("{one_game}\n{constant}\n")).unwrap();
+            let err = read_games(&path).unwrap_err();
+            assert!(err.to_string().contains("constant expression"));
+        }
+    }
+
+    #[test]
+    fn test_read_exps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("exps.txt");
+        fs::write(
+            &path,
+            "# hand-crafted games\n1 2 + 1 2 3 + 4\n\n{\"and\": [1, {\"or\": [2, 3]}]}\n",
+        )
+        .unwrap();
+        let games = read_exps(&path).unwrap();
+        let dnfs: Vec<String> = games.iter().map(|g| g.dnf().to_string()).collect();
+        assert_eq!(dnfs, ["4 + 1 2", "1 2 + 1 3"]);
+        assert_eq!(games[1].owner_len(), 3);
+        // a read-once expression is decomposed without expanding it, others are expanded
+        assert!(games[1].factored().is_some());
+        let game = parse_exp("{\"or\": [{\"and\": [1, 2]}, {\"and\": [1, 3]}]}").unwrap();
+        assert!(game.factored().is_none());
+
+        fs::write(&path, "1 2\nTRUE\n").unwrap();
+        let err = read_exps(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"));
+        assert!(parse_exp("{\"or\": [1, {\"and\": []}]}").is_err());
+        assert!(parse_exp("1 + x").is_err());
+    }
+
+    #[test]
+    fn test_dimacs_games() {
+        let owner_names: OwnerNames = ["acme", "globex", "initech corp"].into_iter().collect();
+        let games = vec![parse_exp("0 1 + 2").unwrap(), parse_exp("1 2").unwrap()];
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("dimacs");
+        write_dimacs_games(&dir, &games, &owner_names).unwrap();
+        let content = fs::read_to_string(dir.join("game-1.dnf")).unwrap();
+        assert_eq!(
+            content,
+            "c v 1 globex\nc v 2 initech corp\np dnf 2 1\n1 2 0\n"
+        );
+
+        // the owners are interned in the order they are read
+        let paths = [dir.join("game-1.dnf"), dir.join("game-0.dnf")];
+        let mut actual_names = OwnerNames::default();
+        let actual = read_dimacs_games(&paths, &mut actual_names).unwrap();
+        assert_eq!(actual_names.names(), ["globex", "initech corp", "acme"]);
+        for (game, actual) in games.iter().rev().zip(&actual) {
+            let dnf = actual
+                .dnf()
+                .map_variable(|&owner| owner_names.id(actual_names.name(owner).unwrap()).unwrap());
+            assert_eq!(&dnf, game.dnf());
+        }
+
+        fs::write(&paths[0], "p dnf 0 0\n").unwrap();
+        let err = read_dimacs_games(&paths, &mut actual_names).unwrap_err();
+        assert!(err.to_string().contains("constant expression"));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{dnf::canonicalize, Dnf, Game, ShapleyValues};
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Upper bound of the implicants of the expansion of a game built from its decomposition for it
+/// to be looked up. Larger ones would cost more to expand and canonicalize than to compute from
+/// their decomposition, so they bypass the cache.
+const MAX_EXPANDED_LEN: usize = 256;
+
+/// A cache of the Shapley values of games by the canonical form of their DNF, shared across
+/// rayon workers.
+///
+/// Games whose DNFs are equal up to renaming their owners have the same Shapley values up to the
+/// same renaming: the values of a form are stored by the position of the owners in it, and
+/// mapped back to the owners of every game of that form.
+#[derive(Debug, Default)]
+pub struct IsoCache {
+    values: DashMap<Dnf<u32>, Vec<f64>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl IsoCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The Shapley values of `game`, computed by `f` unless the values of a game of the same
+    /// form are cached. A game built from its decomposition is expanded only if its expansion
+    /// has at most [`MAX_EXPANDED_LEN`] implicants, and the expansion is not kept in the game.
+    /// Larger ones are computed by `f`, and counted as misses.
+    pub fn shapley_values(
+        &self,
+        game: &Game,
+        f: impl FnOnce(&Game) -> ShapleyValues,
+    ) -> ShapleyValues {
+        let canonical = match game.factored() {
+            Some(d) if d.expanded_len() > MAX_EXPANDED_LEN => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return f(game);
+            }
+            Some(d) => canonicalize(&d.clone().expand()),
+            None => canonicalize(game.dnf()),
+        };
+        if let Some(values) = self.values.get(&canonical.exp) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return canonical
+                .vars
+                .into_iter()
+                .zip(values.iter().copied())
+                .collect();
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let shapley_values = f(game);
+        let values = canonical
+            .vars
+            .iter()
+            .map(|owner| shapley_values.get(owner).copied().unwrap_or_default())
+            .collect();
+        self.values.insert(canonical.exp, values);
+        shapley_values
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// The number of distinct forms cached.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The fraction of games whose Shapley values were found in the cache.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits();
+        let total = hits + self.misses();
+        if total == 0 {
+            0.
+        } else {
+            hits as f64 / total as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alg::traditional::traditional_method, dnf::RecursiveDecompose, tests::assert_f64_eq,
+        OwnerId,
+    };
+
+    #[test]
+    fn test_iso_cache() {
+        let game = |exp: &str| Game::new(exp.parse().unwrap());
+        let games = [
+            game("1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5"),
+            game("19 18 16 + 19 18 17 + 18 15 16 + 18 15 17 + 16 17"),
+            game("1 2 + 3"),
+            game("7 + 8 9"),
+        ];
+
+        let cache = IsoCache::new();
+        for game in &games {
+            let expected = traditional_method(game);
+            let actual = cache.shapley_values(game, traditional_method);
+            assert_eq!(actual.len(), expected.len());
+            for (owner, value) in expected {
+                assert_f64_eq(value, actual[&owner]);
+            }
+        }
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (2, 2, 2));
+        assert_f64_eq(0.5, cache.hit_rate());
+
+        // a cached form does not compute
+        let actual = cache.shapley_values(&game("5 + 6 4"), |_| unreachable!());
+        assert_f64_eq(2. / 3., actual[&OwnerId(5)]);
+        assert_f64_eq(1. / 6., actual[&OwnerId(6)]);
+
+        // small factored games are looked up by their expansion, large ones bypass the cache
+        let var = |owner| RecursiveDecompose::Var(OwnerId(owner));
+        let factored = Game::from_decompose(RecursiveDecompose::Or(vec![
+            var(5),
+            RecursiveDecompose::And(vec![var(6), var(4)]),
+        ]));
+        let actual = cache.shapley_values(&factored, |_| unreachable!());
+        assert_f64_eq(2. / 3., actual[&OwnerId(5)]);
+        let large = Game::from_decompose(RecursiveDecompose::And(
+            (0..5)
+                .map(|i| RecursiveDecompose::Or((0..4).map(|j| var(4 * i + j)).collect()))
+                .collect(),
+        ));
+        assert_eq!(large.factored().unwrap().expanded_len(), 1024);
+        let (hits, misses) = (cache.hits(), cache.misses());
+        let actual = cache.shapley_values(&large, |_| ShapleyValues::default());
+        assert!(actual.is_empty());
+        assert_eq!(
+            (cache.hits(), cache.misses(), cache.len()),
+            (hits, misses + 1, 2)
+        );
+    }
+}
@@ -1,19 +1,564 @@
+use crate::{alg::join::join_schema, DataSet};
+use anyhow::{bail, ensure, Context, Result};
 use once_cell::sync::Lazy;
-use polars_core::prelude::JoinType;
-use std::collections::HashMap;
+use polars::prelude::{col, lit, Expr};
+use polars_core::prelude::{
+    BooleanChunked, ChunkCompare, ChunkFull, DataFrame, JoinType, NamedFrom, Series,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JoinStep {
-    pub table_to_join: &'static str,
-    pub left_join_keys: Vec<&'static str>,
-    pub right_join_keys: Vec<&'static str>,
-    pub join_type: &'static JoinType,
+    pub table_to_join: String,
+    pub left_join_keys: Vec<String>,
+    pub right_join_keys: Vec<String>,
+    #[serde(with = "serde_join_type", default = "default_join_type")]
+    pub join_type: JoinType,
 }
 
-#[derive(Debug, Clone)]
+impl JoinStep {
+    pub fn new<S: Into<String>>(
+        table_to_join: impl Into<String>,
+        left_join_keys: impl IntoIterator<Item = S>,
+        right_join_keys: impl IntoIterator<Item = S>,
+        join_type: JoinType,
+    ) -> Self {
+        Self {
+            table_to_join: table_to_join.into(),
+            left_join_keys: left_join_keys.into_iter().map(Into::into).collect(),
+            right_join_keys: right_join_keys.into_iter().map(Into::into).collect(),
+            join_type,
+        }
+    }
+}
+
+/// Comparison operator of a [`Predicate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CmpOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+/// Right-hand side of a [`Predicate`]: either another column or a literal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Operand {
+    Column { column: String },
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Operand {
+    pub fn column(column: impl Into<String>) -> Self {
+        Self::Column {
+            column: column.into(),
+        }
+    }
+
+    /// The column the operand reads, if any.
+    pub fn as_column(&self) -> Option<&str> {
+        match self {
+            Operand::Column { column } => Some(column),
+            _ => None,
+        }
+    }
+
+    /// The operand as a polars expression.
+    fn to_expr(&self) -> Expr {
+        match self {
+            Operand::Column { column } => col(column),
+            Operand::Int(v) => lit(*v),
+            Operand::Float(v) => lit(*v),
+            Operand::Str(v) => lit(v.as_str()),
+        }
+    }
+
+    /// Broadcast the operand to a series with one value per row of `df`.
+    fn to_series(&self, df: &DataFrame) -> Result<Series> {
+        let len = df.height();
+        Ok(match self {
+            Operand::Column { column } => df.column(column)?.clone(),
+            Operand::Int(v) => Series::new("", vec![*v; len]),
+            Operand::Float(v) => Series::new("", vec![*v; len]),
+            Operand::Str(v) => Series::new("", vec![v.as_str(); len]),
+        })
+    }
+}
+
+/// A comparison `column op value` on the join result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Predicate {
+    pub column: String,
+    pub op: CmpOp,
+    pub value: Operand,
+}
+
+impl Predicate {
+    pub fn new(column: impl Into<String>, op: CmpOp, value: Operand) -> Self {
+        Self {
+            column: column.into(),
+            op,
+            value,
+        }
+    }
+
+    /// Eval the predicate on every row of `df`.
+    pub fn eval(&self, df: &DataFrame) -> Result<BooleanChunked> {
+        fn compare<Rhs>(lhs: &Series, op: CmpOp, rhs: Rhs) -> Result<BooleanChunked>
+        where
+            Series: ChunkCompare<Rhs, Item = polars_core::error::PolarsResult<BooleanChunked>>,
+        {
+            let mask = match op {
+                CmpOp::Eq => lhs.equal(rhs),
+                CmpOp::NotEq => lhs.not_equal(rhs),
+                CmpOp::Lt => lhs.lt(rhs),
+                CmpOp::LtEq => lhs.lt_eq(rhs),
+                CmpOp::Gt => lhs.gt(rhs),
+                CmpOp::GtEq => lhs.gt_eq(rhs),
+            }?;
+            Ok(mask)
+        }
+
+        let lhs = df.column(&self.column)?;
+        let mask = match &self.value {
+            Operand::Column { column } => compare(lhs, self.op, df.column(column)?),
+            Operand::Int(v) => compare(lhs, self.op, *v),
+            Operand::Float(v) => compare(lhs, self.op, *v),
+            Operand::Str(v) => compare(lhs, self.op, v.as_str()),
+        };
+        mask.with_context(|| format!("failed to eval predicate on {}", self.column))
+    }
+
+    /// The predicate as a polars expression, so that it can be pushed down into a lazy join.
+    pub fn to_expr(&self) -> Expr {
+        let lhs = col(&self.column);
+        let rhs = self.value.to_expr();
+        match self.op {
+            CmpOp::Eq => lhs.eq(rhs),
+            CmpOp::NotEq => lhs.neq(rhs),
+            CmpOp::Lt => lhs.lt(rhs),
+            CmpOp::LtEq => lhs.lt_eq(rhs),
+            CmpOp::Gt => lhs.gt(rhs),
+            CmpOp::GtEq => lhs.gt_eq(rhs),
+        }
+    }
+
+    /// The columns the predicate reads.
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.column.as_str()).chain(self.value.as_column())
+    }
+}
+
+/// A branch of a [`DerivedColumn`]: `then` if all predicates in `when` hold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Case {
+    pub when: Vec<Predicate>,
+    pub then: Operand,
+}
+
+/// A column computed on the join result. Its value is the one of the first case that holds,
+/// or `otherwise` if none does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DerivedColumn {
+    pub name: String,
+    #[serde(default)]
+    pub cases: Vec<Case>,
+    pub otherwise: Operand,
+}
+
+impl DerivedColumn {
+    /// Eval the column on every row of `df`.
+    pub fn eval(&self, df: &DataFrame) -> Result<Series> {
+        let mut ans = self.otherwise.to_series(df)?;
+        for case in self.cases.iter().rev() {
+            let mut mask = BooleanChunked::full("", true, df.height());
+            for predicate in &case.when {
+                mask = mask & predicate.eval(df)?;
+            }
+            ans = case
+                .then
+                .to_series(df)?
+                .zip_with(&mask, &ans)
+                .with_context(|| format!("failed to eval derived column {}", self.name))?;
+        }
+        ans.rename(&self.name);
+        Ok(ans)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JoinPlan {
-    pub init_table: &'static str,
+    pub init_table: String,
+    #[serde(default)]
     pub steps: Vec<JoinStep>,
+    /// Columns computed on the join result, in order, before the filters are applied.
+    #[serde(default)]
+    pub derived: Vec<DerivedColumn>,
+    /// Selection predicates applied to the join result.
+    #[serde(default)]
+    pub filters: Vec<Predicate>,
+    /// Output attributes. Join results projected to the same output tuple are merged into one
+    /// game whose provenance is the OR of theirs. If empty, every join result is an output tuple.
+    #[serde(default)]
+    pub projection: Vec<String>,
+    /// Patterns of the columns identifying the owners of a join result, for datasets whose
+    /// ownership is an attribute of the data rather than an assignment. Each pattern is a
+    /// regex matched against the whole column name.
+    #[serde(default)]
+    pub owner_columns: Vec<String>,
+    /// Whether the plan is a boolean query, i.e. whether the database answers it at all.
+    /// All join results are then merged into a single game.
+    #[serde(default)]
+    pub boolean: bool,
+}
+
+impl JoinPlan {
+    pub fn builder() -> JoinPlanBuilder {
+        JoinPlanBuilder::default()
+    }
+
+    /// All tables in the plan, in join order.
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.init_table.as_str())
+            .chain(self.steps.iter().map(|s| s.table_to_join.as_str()))
+    }
+
+    /// Load a join plan from a JSON or TOML file. The format is chosen by the file extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let plan: Self = serde_json::from_value(read_plan_file(path)?)?;
+        plan.validate()
+            .with_context(|| format!("invalid join plan {}", path.display()))?;
+        Ok(plan)
+    }
+
+    /// The columns among `columns` matching any of the owner column patterns, in order.
+    pub fn match_owner_columns<'a>(
+        &self,
+        columns: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<&'a str>> {
+        let patterns = self.owner_column_patterns()?;
+        Ok(columns
+            .into_iter()
+            .filter(|c| patterns.iter().any(|p| p.is_match(c)))
+            .collect())
+    }
+
+    pub(crate) fn owner_column_patterns(&self) -> Result<Vec<Regex>> {
+        self.owner_columns
+            .iter()
+            .map(|p| {
+                Regex::new(&format!("^(?:{p})$"))
+                    .with_context(|| format!("invalid owner column pattern {p}"))
+            })
+            .collect()
+    }
+
+    /// Check that the plan is well-formed before it is executed.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(!self.init_table.is_empty(), "init table is empty");
+
+        let mut tables = HashSet::from([self.init_table.as_str()]);
+        for (i, step) in self.steps.iter().enumerate() {
+            let table = step.table_to_join.as_str();
+            ensure!(!table.is_empty(), "step #{i}: table to join is empty");
+            ensure!(
+                tables.insert(table),
+                "step #{i}: table {table} is joined twice"
+            );
+            ensure!(
+                !step.left_join_keys.is_empty(),
+                "step #{i}: no join key for table {table}"
+            );
+            ensure!(
+                step.left_join_keys.len() == step.right_join_keys.len(),
+                "step #{i}: {} left join keys but {} right join keys for table {table}",
+                step.left_join_keys.len(),
+                step.right_join_keys.len(),
+            );
+            ensure!(
+                matches!(
+                    step.join_type,
+                    JoinType::Inner | JoinType::Left | JoinType::Outer
+                ),
+                "step #{i}: unsupported join type {:?}",
+                step.join_type,
+            );
+        }
+
+        self.owner_column_patterns()?;
+
+        ensure!(
+            !self.boolean || self.projection.is_empty(),
+            "a boolean query has no projection"
+        );
+
+        Ok(())
+    }
+
+    /// Check the plan against the tables of `dataset`: every table must exist, and every pair
+    /// of join keys must exist with the same dtype.
+    pub fn validate_schema(&self, dataset: &DataSet) -> Result<()> {
+        self.validate()?;
+        join_schema(
+            |table_name| dataset.tables.get(table_name).map(|t| &t.df),
+            self,
+            !dataset.owner_set.is_empty(),
+        )?;
+        Ok(())
+    }
+}
+
+/// Read a JSON or TOML plan file. The format is chosen by the file extension.
+fn read_plan_file(path: &Path) -> Result<serde_json::Value> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read join plan {}", path.display()))?;
+    Ok(match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&content)?,
+        Some("toml") => toml::from_str(&content)?,
+        _ => bail!("unsupported join plan format: {}", path.display()),
+    })
+}
+
+/// A union of join plans sharing the same output key. The provenance of an output tuple is
+/// the OR of its provenance in every branch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnionPlan {
+    pub branches: Vec<JoinPlan>,
+    /// Output attributes, which every branch must have. If empty, the union is a boolean query.
+    #[serde(default)]
+    pub output_key: Vec<String>,
+}
+
+impl UnionPlan {
+    /// The branches as they are executed: projected onto the output key, or as boolean queries
+    /// if there is no output key.
+    pub fn branches(&self) -> impl Iterator<Item = JoinPlan> + '_ {
+        self.branches.iter().map(|branch| JoinPlan {
+            projection: self.output_key.clone(),
+            boolean: self.output_key.is_empty(),
+            ..branch.clone()
+        })
+    }
+
+    /// Check that the plan is well-formed before it is executed.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(!self.branches.is_empty(), "union has no branch");
+        for (i, (branch, plan)) in self.branches.iter().zip(self.branches()).enumerate() {
+            ensure!(
+                branch.projection.is_empty() || branch.projection == self.output_key,
+                "branch #{i}: projection differs from the output key of the union"
+            );
+            plan.validate()
+                .with_context(|| format!("invalid branch #{i}"))?;
+        }
+        Ok(())
+    }
+
+    /// Check the plan against the tables of `dataset`: every branch must be valid, and the
+    /// output key must have the same dtypes in every branch.
+    pub fn validate_schema(&self, dataset: &DataSet) -> Result<()> {
+        self.validate()?;
+        let mut key_dtypes = None;
+        for (i, branch) in self.branches().enumerate() {
+            let schema = join_schema(
+                |table_name| dataset.tables.get(table_name).map(|t| &t.df),
+                &branch,
+                !dataset.owner_set.is_empty(),
+            )
+            .with_context(|| format!("invalid branch #{i}"))?;
+            let dtypes: Vec<_> = self
+                .output_key
+                .iter()
+                .map(|column| schema.get(column).cloned())
+                .collect();
+            match &key_dtypes {
+                None => key_dtypes = Some(dtypes),
+                Some(expect) => ensure!(
+                    expect == &dtypes,
+                    "branch #{i}: output key has dtypes {dtypes:?} but branch #0 has {expect:?}"
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A plan file: either a [`JoinPlan`] or a [`UnionPlan`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Plan {
+    Union(UnionPlan),
+    Join(JoinPlan),
+}
+
+impl Plan {
+    /// Load a plan from a JSON or TOML file. Plans with `branches` are unions.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let value = read_plan_file(path)?;
+        let plan = if value.get("branches").is_some() {
+            let plan: UnionPlan = serde_json::from_value(value)?;
+            plan.validate()
+                .with_context(|| format!("invalid union plan {}", path.display()))?;
+            Self::Union(plan)
+        } else {
+            let plan: JoinPlan = serde_json::from_value(value)?;
+            plan.validate()
+                .with_context(|| format!("invalid join plan {}", path.display()))?;
+            Self::Join(plan)
+        };
+        Ok(plan)
+    }
+
+    pub fn validate_schema(&self, dataset: &DataSet) -> Result<()> {
+        match self {
+            Self::Union(plan) => plan.validate_schema(dataset),
+            Self::Join(plan) => plan.validate_schema(dataset),
+        }
+    }
+
+    /// Turn the plan into a boolean query.
+    pub fn set_boolean(&mut self) {
+        match self {
+            Self::Union(plan) => plan.output_key.clear(),
+            Self::Join(plan) => {
+                plan.projection.clear();
+                plan.boolean = true;
+            }
+        }
+    }
+}
+
+/// Builder of [`JoinPlan`].
+#[derive(Debug, Clone, Default)]
+pub struct JoinPlanBuilder {
+    init_table: String,
+    steps: Vec<JoinStep>,
+    derived: Vec<DerivedColumn>,
+    filters: Vec<Predicate>,
+    projection: Vec<String>,
+    owner_columns: Vec<String>,
+    boolean: bool,
+}
+
+impl JoinPlanBuilder {
+    pub fn init_table(mut self, init_table: impl Into<String>) -> Self {
+        self.init_table = init_table.into();
+        self
+    }
+
+    pub fn join<S: Into<String>>(
+        mut self,
+        table_to_join: impl Into<String>,
+        left_join_keys: impl IntoIterator<Item = S>,
+        right_join_keys: impl IntoIterator<Item = S>,
+        join_type: JoinType,
+    ) -> Self {
+        self.steps.push(JoinStep::new(
+            table_to_join,
+            left_join_keys,
+            right_join_keys,
+            join_type,
+        ));
+        self
+    }
+
+    pub fn step(mut self, step: JoinStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn derive(mut self, column: DerivedColumn) -> Self {
+        self.derived.push(column);
+        self
+    }
+
+    pub fn filter(mut self, predicate: Predicate) -> Self {
+        self.filters.push(predicate);
+        self
+    }
+
+    pub fn project<S: Into<String>>(mut self, columns: impl IntoIterator<Item = S>) -> Self {
+        self.projection.extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn owner_columns<S: Into<String>>(mut self, patterns: impl IntoIterator<Item = S>) -> Self {
+        self.owner_columns
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn boolean(mut self, boolean: bool) -> Self {
+        self.boolean = boolean;
+        self
+    }
+
+    /// Build the plan after checking that it is well-formed.
+    pub fn build(self) -> Result<JoinPlan> {
+        let plan = JoinPlan {
+            init_table: self.init_table,
+            steps: self.steps,
+            derived: self.derived,
+            filters: self.filters,
+            projection: self.projection,
+            owner_columns: self.owner_columns,
+            boolean: self.boolean,
+        };
+        plan.validate()?;
+        Ok(plan)
+    }
+
+    /// Build the plan after checking it against the tables of `dataset`.
+    pub fn build_for(self, dataset: &DataSet) -> Result<JoinPlan> {
+        let plan = self.build()?;
+        plan.validate_schema(dataset)?;
+        Ok(plan)
+    }
+}
+
+fn default_join_type() -> JoinType {
+    JoinType::Inner
+}
+
+mod serde_join_type {
+    use super::*;
+    use serde::{de::Deserializer, ser::Serializer};
+
+    pub fn serialize<S: Serializer>(t: &JoinType, s: S) -> Result<S::Ok, S::Error> {
+        let name = match t {
+            JoinType::Inner => "inner",
+            JoinType::Left => "left",
+            JoinType::Outer => "outer",
+            _ => return Err(serde::ser::Error::custom(format!("{t:?}"))),
+        };
+        s.serialize_str(name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<JoinType, D::Error> {
+        let name = <String>::deserialize(d)?;
+        match name.to_lowercase().as_str() {
+            "inner" => Ok(JoinType::Inner),
+            "left" => Ok(JoinType::Left),
+            "outer" => Ok(JoinType::Outer),
+            _ => Err(serde::de::Error::custom(format!(
+                "unknown join type: {name}"
+            ))),
+        }
+    }
 }
 
 pub static PLANS: Lazy<HashMap<&'static str, JoinPlan>> = Lazy::new(|| {
@@ -21,86 +566,259 @@ pub static PLANS: Lazy<HashMap<&'static str, JoinPlan>> = Lazy::new(|| {
     plans.insert(
         "tpch",
         JoinPlan {
-            init_table: "region",
+            init_table: "region".to_string(),
             steps: vec![
-                JoinStep {
-                    table_to_join: "nation",
-                    left_join_keys: vec!["r_regionkey"],
-                    right_join_keys: vec!["n_regionkey"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "supplier",
-                    left_join_keys: vec!["n_nationkey"],
-                    right_join_keys: vec!["s_nationkey"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "partsupp",
-                    left_join_keys: vec!["s_suppkey"],
-                    right_join_keys: vec!["ps_suppkey"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "part",
-                    left_join_keys: vec!["ps_partkey"],
-                    right_join_keys: vec!["p_partkey"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "lineitem",
-                    left_join_keys: vec!["p_partkey", "ps_suppkey"],
-                    right_join_keys: vec!["l_partkey", "l_suppkey"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "orders",
-                    left_join_keys: vec!["l_orderkey"],
-                    right_join_keys: vec!["o_orderkey"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "customer",
-                    left_join_keys: vec!["o_custkey"],
-                    right_join_keys: vec!["c_custkey"],
-                    join_type: &JoinType::Inner,
-                },
+                JoinStep::new("nation", ["r_regionkey"], ["n_regionkey"], JoinType::Inner),
+                JoinStep::new(
+                    "supplier",
+                    ["n_nationkey"],
+                    ["s_nationkey"],
+                    JoinType::Inner,
+                ),
+                JoinStep::new("partsupp", ["s_suppkey"], ["ps_suppkey"], JoinType::Inner),
+                JoinStep::new("part", ["ps_partkey"], ["p_partkey"], JoinType::Inner),
+                JoinStep::new(
+                    "lineitem",
+                    ["p_partkey", "ps_suppkey"],
+                    ["l_partkey", "l_suppkey"],
+                    JoinType::Inner,
+                ),
+                JoinStep::new("orders", ["l_orderkey"], ["o_orderkey"], JoinType::Inner),
+                JoinStep::new("customer", ["o_custkey"], ["c_custkey"], JoinType::Inner),
             ],
+            ..Default::default()
         },
     );
 
     plans.insert(
         "soccer",
         JoinPlan {
-            init_table: "Match",
+            init_table: "Match".to_string(),
             steps: vec![
-                JoinStep {
-                    table_to_join: "Country",
-                    left_join_keys: vec!["country_id"],
-                    right_join_keys: vec!["id"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "HomeTeam",
-                    left_join_keys: vec!["home_team_api_id"],
-                    right_join_keys: vec!["team_api_id"],
-                    join_type: &JoinType::Inner,
-                },
-                JoinStep {
-                    table_to_join: "AwayTeam",
-                    left_join_keys: vec!["away_team_api_id"],
-                    right_join_keys: vec!["team_api_id"],
-                    join_type: &JoinType::Left,
-                },
-                JoinStep {
-                    table_to_join: "League",
-                    left_join_keys: vec!["league_id"],
-                    right_join_keys: vec!["id"],
-                    join_type: &JoinType::Inner,
-                },
+                JoinStep::new("Country", ["country_id"], ["id"], JoinType::Inner),
+                JoinStep::new(
+                    "HomeTeam",
+                    ["home_team_api_id"],
+                    ["team_api_id"],
+                    JoinType::Inner,
+                ),
+                JoinStep::new(
+                    "AwayTeam",
+                    ["away_team_api_id"],
+                    ["team_api_id"],
+                    JoinType::Left,
+                ),
+                JoinStep::new("League", ["league_id"], ["id"], JoinType::Inner),
+            ],
+            derived: vec![DerivedColumn {
+                name: "winner".to_string(),
+                cases: vec![
+                    Case {
+                        when: vec![Predicate::new(
+                            "home_team_goal",
+                            CmpOp::Gt,
+                            Operand::column("away_team_goal"),
+                        )],
+                        then: Operand::column("team_long_name"),
+                    },
+                    Case {
+                        when: vec![Predicate::new(
+                            "home_team_goal",
+                            CmpOp::Lt,
+                            Operand::column("away_team_goal"),
+                        )],
+                        then: Operand::column("team_long_name:AwayTeam"),
+                    },
+                ],
+                otherwise: Operand::Str("DRAW".to_string()),
+            }],
+            filters: vec![
+                Predicate::new("season", CmpOp::Eq, Operand::Str("2015/2016".to_string())),
+                Predicate::new("winner", CmpOp::NotEq, Operand::Str("DRAW".to_string())),
             ],
+            projection: vec!["winner".to_string()],
+            owner_columns: vec![".*api_id.*".to_string()],
+            ..Default::default()
         },
     );
 
     plans
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::fixture_dataset;
+
+    #[test]
+    fn test_builtin_plans() {
+        for plan in PLANS.values() {
+            plan.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let json = r#"{
+            "init_table": "region",
+            "steps": [
+                { "table_to_join": "nation", "left_join_keys": ["r_regionkey"], "right_join_keys": ["n_regionkey"] },
+                { "table_to_join": "supplier", "left_join_keys": ["n_nationkey"], "right_join_keys": ["s_nationkey"], "join_type": "left" }
+            ]
+        }"#;
+        let plan: JoinPlan = serde_json::from_str(json).unwrap();
+        plan.validate().unwrap();
+        assert_eq!(plan.steps[0].join_type, JoinType::Inner);
+        assert_eq!(plan.steps[1].join_type, JoinType::Left);
+
+        let toml = r#"
+            init_table = "region"
+            filters = [{ column = "r_name", op = "not_eq", value = "ASIA" }]
+
+            [[derived]]
+            name = "big"
+            otherwise = 0
+            cases = [{ when = [{ column = "r_regionkey", op = "gt", value = { column = "n_nationkey" } }], then = 1 }]
+
+            [[steps]]
+            table_to_join = "nation"
+            left_join_keys = ["r_regionkey"]
+            right_join_keys = ["n_regionkey"]
+            join_type = "outer"
+        "#;
+        let plan: JoinPlan = toml::from_str(toml).unwrap();
+        plan.validate().unwrap();
+        assert_eq!(plan.steps[0].join_type, JoinType::Outer);
+        assert_eq!(
+            plan.filters,
+            vec![Predicate::new(
+                "r_name",
+                CmpOp::NotEq,
+                Operand::Str("ASIA".to_string())
+            )]
+        );
+        assert_eq!(
+            plan.derived[0].cases[0].when[0].value,
+            Operand::column("n_nationkey")
+        );
+        assert_eq!(plan.derived[0].otherwise, Operand::Int(0));
+
+        let json = serde_json::to_string(&plan).unwrap();
+        let plan2: JoinPlan = serde_json::from_str(&json).unwrap();
+        assert_eq!(plan2.steps[0].join_type, JoinType::Outer);
+    }
+
+    #[test]
+    fn test_validate() {
+        let plan = JoinPlan {
+            init_table: "region".to_string(),
+            steps: vec![JoinStep::new(
+                "nation",
+                ["r_regionkey"],
+                ["n_regionkey", "n_nationkey"],
+                JoinType::Inner,
+            )],
+            ..Default::default()
+        };
+        assert!(plan.validate().is_err());
+
+        let plan = JoinPlan {
+            init_table: "region".to_string(),
+            steps: vec![JoinStep::new(
+                "region",
+                ["r_regionkey"],
+                ["r_regionkey"],
+                JoinType::Inner,
+            )],
+            ..Default::default()
+        };
+        assert!(plan.validate().is_err());
+
+        let json = r#"{ "init_table": "a", "steps": [{ "table_to_join": "b", "left_join_keys": ["x"], "right_join_keys": ["y"], "join_type": "cross" }] }"#;
+        assert!(serde_json::from_str::<JoinPlan>(json).is_err());
+
+        let plan = JoinPlan {
+            init_table: "region".to_string(),
+            owner_columns: vec!["(api_id".to_string()],
+            ..Default::default()
+        };
+        assert!(plan.validate().is_err());
+
+        let plan = &PLANS["soccer"];
+        assert_eq!(
+            plan.match_owner_columns(["home_team_api_id", "team_api_id:AwayTeam", "api_idx", "id"])
+                .unwrap(),
+            vec!["home_team_api_id", "team_api_id:AwayTeam", "api_idx"]
+        );
+    }
+
+    #[test]
+    fn test_union_plan() {
+        let dataset = fixture_dataset();
+        let branch = |key: Operand| {
+            JoinPlan::builder()
+                .init_table("customer")
+                .derive(DerivedColumn {
+                    name: "key".to_string(),
+                    cases: vec![],
+                    otherwise: key,
+                })
+                .build()
+                .unwrap()
+        };
+
+        let mut plan = UnionPlan {
+            branches: vec![branch(Operand::column("c_name")), branch(Operand::Int(1))],
+            output_key: vec!["key".to_string()],
+        };
+        plan.validate().unwrap();
+        let err = plan.validate_schema(&dataset).unwrap_err();
+        assert!(format!("{err:#}").contains("branch #1: output key has dtypes"));
+
+        plan.branches[1] = branch(Operand::Str("bob".to_string()));
+        plan.validate_schema(&dataset).unwrap();
+
+        plan.branches[1].projection = vec!["c_name".to_string()];
+        assert!(plan.validate().is_err());
+
+        assert!(UnionPlan::default().validate().is_err());
+
+        let json = r#"{ "output_key": ["key"], "branches": [{ "init_table": "customer" }] }"#;
+        let plan: UnionPlan = serde_json::from_str(json).unwrap();
+        assert_eq!(plan.branches().next().unwrap().projection, vec!["key"]);
+    }
+
+    #[test]
+    fn test_builder() {
+        let dataset = fixture_dataset();
+        let builder = || JoinPlan::builder().init_table("customer");
+
+        let plan = builder()
+            .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Inner)
+            .build_for(&dataset)
+            .unwrap();
+        assert_eq!(plan.steps.len(), 1);
+
+        let err = builder()
+            .join("order", ["c_custkey"], ["o_custkey"], JoinType::Inner)
+            .build_for(&dataset)
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot find table order"));
+
+        let err = builder()
+            .join("orders", ["c_custkey"], ["o_custky"], JoinType::Inner)
+            .build_for(&dataset)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("cannot find right join key o_custky"));
+
+        let err = builder()
+            .join("orders", ["c_name"], ["o_custkey"], JoinType::Inner)
+            .build_for(&dataset)
+            .unwrap_err();
+        assert!(err.to_string().contains("different dtypes"));
+
+        assert!(JoinPlan::builder().build().is_err());
+    }
+}
@@ -5,12 +5,19 @@ use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, time::Duration};
 
 pub mod alg;
+pub mod assign;
+pub mod cache;
 pub mod dnf;
 pub mod game;
+pub mod game_store;
+pub mod iso_cache;
 pub mod owner;
 pub mod product_tree;
+pub mod sql;
+pub mod tpch;
 pub mod union_combination;
 pub mod utils;
+pub mod validate;
 
 pub mod table;
 pub use table::*;
@@ -26,7 +33,7 @@ pub(crate) mod tests;
 
 pub use dnf::Dnf;
 pub use game::Game;
-pub use owner::{OwnerId, OwnerSet};
+pub use owner::{OwnerId, OwnerNames, OwnerSet};
 pub type ShapleyValues = HashMap<OwnerId, f64>;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -39,7 +46,8 @@ pub struct SVResult {
     pub load_time: Duration,
     #[serde(with = "serde_time")]
     pub sv_cal_time: Duration,
-    pub shapley_values: ShapleyValues,
+    /// Shapley values by owner name, see [`OwnerNames::display`].
+    pub shapley_values: HashMap<String, f64>,
     pub num_of_owners: usize,
 }
 
@@ -1,5 +1,6 @@
+use crate::ShapleyValues;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 
 #[derive(
     Debug,
@@ -14,6 +15,7 @@ use std::collections::BTreeSet;
     Serialize,
     Deserialize,
     derive_more::Display,
+    derive_more::FromStr,
     derive_more::Constructor,
     derive_more::Deref,
     derive_more::DerefMut,
@@ -24,8 +26,14 @@ use std::collections::BTreeSet;
 )]
 #[as_ref(forward)]
 #[as_mut(forward)]
+#[repr(transparent)]
 pub struct OwnerId(pub u32);
 
+// Safety: a transparent wrapper of `u32`, so that owner maps can be read in place from a
+// memory-mapped file.
+unsafe impl bytemuck::Zeroable for OwnerId {}
+unsafe impl bytemuck::Pod for OwnerId {}
+
 #[derive(
     Debug,
     Default,
@@ -63,3 +71,89 @@ impl FromIterator<OwnerId> for OwnerSet {
         Self(iter.into_iter().collect())
     }
 }
+
+/// Interning table of owner names: owner `OwnerId(i)` is the `i`-th interned name.
+///
+/// Assignments identify owners by arbitrary strings (numeric ids are interned by their decimal
+/// text), which are mapped to dense [`OwnerId`]s when they are loaded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OwnerNames {
+    names: Vec<String>,
+    ids: HashMap<String, OwnerId>,
+}
+
+impl OwnerNames {
+    /// The id of owner `name`, interning it if it is new.
+    pub fn intern(&mut self, name: &str) -> OwnerId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = OwnerId(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<OwnerId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: OwnerId) -> Option<&str> {
+        self.names.get(id.0 as usize).map(String::as_str)
+    }
+
+    /// The name of `id`, or the id itself for owners that were not interned, such as the
+    /// owners read from owner columns.
+    pub fn display(&self, id: OwnerId) -> String {
+        self.name(id).map_or_else(|| id.to_string(), str::to_string)
+    }
+
+    /// Key `values` by owner name.
+    pub fn name_values(&self, values: &ShapleyValues) -> HashMap<String, f64> {
+        values
+            .iter()
+            .map(|(&id, &value)| (self.display(id), value))
+            .collect()
+    }
+
+    /// All interned names, in the order of their ids.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for OwnerNames {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        let mut names = Self::default();
+        for name in iter {
+            names.intern(name.as_ref());
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_names() {
+        let mut names = OwnerNames::default();
+        assert_eq!(names.intern("acme"), OwnerId(0));
+        assert_eq!(names.intern("42"), OwnerId(1));
+        assert_eq!(names.intern("acme"), OwnerId(0));
+        assert_eq!(names.id("42"), Some(OwnerId(1)));
+        assert_eq!(names.name(OwnerId(1)), Some("42"));
+        assert_eq!(names.display(OwnerId(0)), "acme");
+        assert_eq!(names.display(OwnerId(7)), "7");
+        assert_eq!(names, OwnerNames::from_iter(["acme", "42", "acme"]));
+    }
+}
@@ -0,0 +1,653 @@
+//! A SQL front-end compiling conjunctive select-project-join queries into [`JoinPlan`]s.
+//!
+//! Supported queries have the form
+//! `SELECT c1, c2 FROM t1, t2 [LEFT | FULL] JOIN t3 ON ... WHERE c1 = c3 AND c4 > 10 AND ...`,
+//! where equalities between columns of different tables become join keys and every other
+//! comparison becomes a selection predicate on the join result. The selected columns become
+//! the projection of the plan; `SELECT *` keeps every join result as an output tuple.
+
+use crate::{CmpOp, DataSet, JoinPlan, JoinStep, Operand, Predicate};
+use anyhow::{bail, ensure, Context, Result};
+use polars_core::prelude::JoinType;
+use sqlparser::{
+    ast::{
+        BinaryOperator, Expr, Ident, JoinConstraint, JoinOperator, SelectItem, SetExpr, Statement,
+        TableFactor, UnaryOperator, Value,
+    },
+    dialect::GenericDialect,
+    parser::Parser,
+};
+use std::collections::{HashMap, HashSet};
+
+/// A column of a table in the query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ColumnRef {
+    table: String,
+    column: String,
+}
+
+#[derive(Debug, Clone)]
+enum Rhs {
+    Column(ColumnRef),
+    Literal(Operand),
+}
+
+#[derive(Debug, Clone)]
+struct Comparison {
+    lhs: ColumnRef,
+    op: CmpOp,
+    rhs: Rhs,
+}
+
+impl Comparison {
+    /// Return the pair of columns if it is an equality between columns of two tables.
+    fn as_join_edge(&self) -> Option<(&ColumnRef, &ColumnRef)> {
+        match (&self.op, &self.rhs) {
+            (CmpOp::Eq, Rhs::Column(rhs)) if rhs.table != self.lhs.table => Some((&self.lhs, rhs)),
+            _ => None,
+        }
+    }
+}
+
+/// A table in the FROM clause.
+#[derive(Debug)]
+struct Relation {
+    table: String,
+    join_type: JoinType,
+    /// Conditions in the `ON` clause of an outer join.
+    on: Vec<Comparison>,
+}
+
+/// Compile a SQL query over the tables of `dataset` into a join plan.
+pub fn compile(sql: &str, dataset: &DataSet) -> Result<JoinPlan> {
+    let mut statements = Parser::parse_sql(&GenericDialect {}, sql)?;
+    ensure!(statements.len() == 1, "expect exactly one SQL statement");
+    let query = match statements.pop().unwrap() {
+        Statement::Query(query) => query,
+        statement => bail!("unsupported SQL statement: {statement}"),
+    };
+    ensure!(
+        query.with.is_none()
+            && query.order_by.is_empty()
+            && query.limit.is_none()
+            && query.offset.is_none()
+            && query.fetch.is_none(),
+        "only SELECT-FROM-WHERE queries are supported"
+    );
+    let select = match *query.body {
+        SetExpr::Select(select) => select,
+        body => bail!("unsupported SQL query: {body}"),
+    };
+    ensure!(
+        select.group_by.is_empty() && select.having.is_none() && select.top.is_none(),
+        "aggregations are not supported"
+    );
+    ensure!(
+        select.into.is_none() && select.lateral_views.is_empty(),
+        "unsupported SQL query: {select}"
+    );
+
+    let mut compiler = Compiler::new(dataset);
+
+    let mut relations = vec![];
+    let mut on_clauses = vec![];
+    for table_with_joins in &select.from {
+        relations.push(compiler.add_table(&table_with_joins.relation, JoinType::Inner)?);
+        for join in &table_with_joins.joins {
+            let (join_type, constraint) = match &join.join_operator {
+                JoinOperator::Inner(c) => (JoinType::Inner, c),
+                JoinOperator::LeftOuter(c) => (JoinType::Left, c),
+                JoinOperator::FullOuter(c) => (JoinType::Outer, c),
+                op => bail!("unsupported join: {op:?}"),
+            };
+            let on = match constraint {
+                JoinConstraint::On(expr) => expr,
+                c => bail!("unsupported join constraint: {c:?}"),
+            };
+            relations.push(compiler.add_table(&join.relation, join_type)?);
+            on_clauses.push((relations.len() - 1, on));
+        }
+    }
+
+    let mut projection = vec![];
+    for item in &select.projection {
+        let expr = match item {
+            SelectItem::Wildcard(_) => {
+                ensure!(
+                    select.projection.len() == 1,
+                    "cannot mix * with other columns in SELECT"
+                );
+                continue;
+            }
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => expr,
+            _ => bail!("unsupported projection: {item}"),
+        };
+        let column = compiler
+            .column(expr)?
+            .with_context(|| format!("unsupported projection: {expr}"))?;
+        projection.push(column);
+    }
+
+    let mut comparisons = vec![];
+    if let Some(selection) = &select.selection {
+        for expr in conjuncts(selection) {
+            comparisons.push(compiler.comparison(expr)?);
+        }
+    }
+    for (i, on) in on_clauses {
+        for expr in conjuncts(on) {
+            let comparison = compiler.comparison(expr)?;
+            if matches!(relations[i].join_type, JoinType::Inner) {
+                comparisons.push(comparison);
+            } else {
+                ensure!(
+                    comparison.as_join_edge().is_some(),
+                    "only equi-join conditions are supported in the ON clause of an outer join"
+                );
+                relations[i].on.push(comparison);
+            }
+        }
+    }
+
+    compiler.plan(relations, comparisons, projection)
+}
+
+/// Split a conjunction into its conjuncts.
+fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            let mut ans = conjuncts(left);
+            ans.extend(conjuncts(right));
+            ans
+        }
+        Expr::Nested(expr) => conjuncts(expr),
+        _ => vec![expr],
+    }
+}
+
+fn cmp_op(op: &BinaryOperator) -> Option<CmpOp> {
+    Some(match op {
+        BinaryOperator::Eq => CmpOp::Eq,
+        BinaryOperator::NotEq => CmpOp::NotEq,
+        BinaryOperator::Lt => CmpOp::Lt,
+        BinaryOperator::LtEq => CmpOp::LtEq,
+        BinaryOperator::Gt => CmpOp::Gt,
+        BinaryOperator::GtEq => CmpOp::GtEq,
+        _ => return None,
+    })
+}
+
+/// The operator with its operands swapped, i.e., `a op b` iff `b flip(op) a`.
+fn flip(op: CmpOp) -> CmpOp {
+    match op {
+        CmpOp::Eq | CmpOp::NotEq => op,
+        CmpOp::Lt => CmpOp::Gt,
+        CmpOp::LtEq => CmpOp::GtEq,
+        CmpOp::Gt => CmpOp::Lt,
+        CmpOp::GtEq => CmpOp::LtEq,
+    }
+}
+
+fn literal(expr: &Expr) -> Result<Operand> {
+    Ok(match expr {
+        Expr::Value(Value::Number(n, _)) => match n.parse::<i64>() {
+            Ok(v) => Operand::Int(v),
+            Err(_) => Operand::Float(n.parse().with_context(|| format!("bad number: {n}"))?),
+        },
+        Expr::Value(Value::SingleQuotedString(s) | Value::DoubleQuotedString(s)) => {
+            Operand::Str(s.clone())
+        }
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => match literal(expr)? {
+            Operand::Int(v) => Operand::Int(-v),
+            Operand::Float(v) => Operand::Float(-v),
+            _ => bail!("unsupported literal: {expr}"),
+        },
+        Expr::Nested(expr) => literal(expr)?,
+        _ => bail!("unsupported literal: {expr}"),
+    })
+}
+
+struct Compiler<'a> {
+    dataset: &'a DataSet,
+    /// Map table names and aliases to table names.
+    aliases: HashMap<String, String>,
+    /// Tables in the FROM clause.
+    tables: Vec<String>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(dataset: &'a DataSet) -> Self {
+        Self {
+            dataset,
+            aliases: HashMap::new(),
+            tables: vec![],
+        }
+    }
+
+    fn add_table(&mut self, factor: &TableFactor, join_type: JoinType) -> Result<Relation> {
+        let (name, alias) = match factor {
+            TableFactor::Table {
+                name,
+                alias,
+                args: None,
+                ..
+            } => (name, alias),
+            _ => bail!("unsupported table: {factor}"),
+        };
+        let table = name.0.iter().map(|i| i.value.as_str()).collect::<Vec<_>>();
+        ensure!(table.len() == 1, "unsupported table name: {name}");
+        let table = table[0].to_string();
+        ensure!(
+            self.dataset.tables.contains_key(&table),
+            "cannot find table {table}"
+        );
+        ensure!(
+            !self.tables.contains(&table),
+            "table {table} appears more than once"
+        );
+
+        for name in [Some(&table), alias.as_ref().map(|a| &a.name.value)]
+            .into_iter()
+            .flatten()
+        {
+            ensure!(
+                self.aliases.insert(name.clone(), table.clone()).is_none(),
+                "duplicate table name or alias {name}"
+            );
+        }
+        self.tables.push(table.clone());
+
+        Ok(Relation {
+            table,
+            join_type,
+            on: vec![],
+        })
+    }
+
+    fn has_column(&self, table: &str, column: &str) -> bool {
+        self.dataset.tables[table].df.schema().get(column).is_some()
+    }
+
+    fn column(&self, expr: &Expr) -> Result<Option<ColumnRef>> {
+        let resolve = |idents: &[Ident]| -> Result<ColumnRef> {
+            match idents {
+                [column] => {
+                    let column = &column.value;
+                    let tables: Vec<_> = self
+                        .tables
+                        .iter()
+                        .filter(|t| self.has_column(t, column))
+                        .collect();
+                    match tables[..] {
+                        [table] => Ok(ColumnRef {
+                            table: table.clone(),
+                            column: column.clone(),
+                        }),
+                        [] => bail!("cannot find column {column}"),
+                        _ => bail!("column {column} is ambiguous"),
+                    }
+                }
+                [table, column] => {
+                    let table = self
+                        .aliases
+                        .get(&table.value)
+                        .with_context(|| format!("cannot find table {}", table.value))?;
+                    ensure!(
+                        self.has_column(table, &column.value),
+                        "cannot find column {}.{}",
+                        table,
+                        column.value
+                    );
+                    Ok(ColumnRef {
+                        table: table.clone(),
+                        column: column.value.clone(),
+                    })
+                }
+                _ => bail!("unsupported column: {expr}"),
+            }
+        };
+
+        Ok(match expr {
+            Expr::Identifier(ident) => Some(resolve(std::slice::from_ref(ident))?),
+            Expr::CompoundIdentifier(idents) => Some(resolve(idents)?),
+            Expr::Nested(expr) => self.column(expr)?,
+            _ => None,
+        })
+    }
+
+    fn comparison(&self, expr: &Expr) -> Result<Comparison> {
+        let (left, op, right) = match expr {
+            Expr::BinaryOp { left, op, right } => (left, op, right),
+            _ => bail!("unsupported condition: {expr}"),
+        };
+        let op = cmp_op(op).with_context(|| format!("unsupported condition: {expr}"))?;
+
+        Ok(match (self.column(left)?, self.column(right)?) {
+            (Some(lhs), Some(rhs)) => Comparison {
+                lhs,
+                op,
+                rhs: Rhs::Column(rhs),
+            },
+            (Some(lhs), None) => Comparison {
+                lhs,
+                op,
+                rhs: Rhs::Literal(literal(right)?),
+            },
+            (None, Some(rhs)) => Comparison {
+                lhs: rhs,
+                op: flip(op),
+                rhs: Rhs::Literal(literal(left)?),
+            },
+            (None, None) => bail!("unsupported condition: {expr}"),
+        })
+    }
+
+    fn plan(
+        &self,
+        relations: Vec<Relation>,
+        comparisons: Vec<Comparison>,
+        projection: Vec<ColumnRef>,
+    ) -> Result<JoinPlan> {
+        let is_assignment = !self.dataset.owner_set.is_empty();
+
+        let mut edges = vec![];
+        let mut filters = vec![];
+        for c in comparisons {
+            if c.as_join_edge().is_some() {
+                edges.push(c);
+            } else {
+                filters.push(c);
+            }
+        }
+
+        let mut relations = relations.into_iter();
+        let init = relations.next().context("no table in the FROM clause")?;
+        ensure!(
+            matches!(init.join_type, JoinType::Inner),
+            "the first table cannot be outer joined"
+        );
+        let mut naming = Naming::new(self.dataset, &init.table);
+        let mut steps = vec![];
+        let mut pending: Vec<Relation> = vec![];
+
+        for relation in relations {
+            if matches!(relation.join_type, JoinType::Inner) {
+                pending.push(relation);
+            } else {
+                ensure!(
+                    pending.is_empty(),
+                    "no join condition connects table {} to the preceding tables",
+                    pending[0].table
+                );
+                let keys = join_keys(&naming.joined, &relation.table, relation.on)?;
+                ensure!(
+                    !keys.is_empty(),
+                    "no join condition in the ON clause of table {}",
+                    relation.table
+                );
+                steps.push(naming.join(
+                    &relation.table,
+                    keys,
+                    relation.join_type,
+                    is_assignment,
+                )?);
+            }
+
+            // join pending tables as long as they are connected to the joined ones
+            while let Some(i) = pending.iter().position(|r| {
+                edges
+                    .iter()
+                    .filter_map(Comparison::as_join_edge)
+                    .any(|(a, b)| connects(&naming.joined, &r.table, a, b))
+            }) {
+                let relation = pending.remove(i);
+                let (keys, rest): (Vec<_>, Vec<_>) = edges.into_iter().partition(|c| {
+                    let (a, b) = c.as_join_edge().unwrap();
+                    connects(&naming.joined, &relation.table, a, b)
+                });
+                edges = rest;
+                let keys = join_keys(&naming.joined, &relation.table, keys)?;
+                steps.push(naming.join(&relation.table, keys, JoinType::Inner, is_assignment)?);
+            }
+        }
+        if let Some(relation) = pending.first() {
+            bail!(
+                "no join condition connects table {}, cross products are not supported",
+                relation.table
+            );
+        }
+
+        // equalities not used as join keys are checked after the join
+        filters.extend(edges);
+        let filters = filters
+            .into_iter()
+            .map(|c| {
+                let value = match c.rhs {
+                    Rhs::Column(column) => Operand::Column {
+                        column: naming.name(&column)?,
+                    },
+                    Rhs::Literal(value) => value,
+                };
+                Ok(Predicate::new(naming.name(&c.lhs)?, c.op, value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let projection = projection
+            .iter()
+            .map(|c| naming.name(c))
+            .collect::<Result<Vec<_>>>()?;
+
+        let plan = JoinPlan {
+            init_table: init.table,
+            steps,
+            derived: vec![],
+            filters,
+            projection,
+            owner_columns: vec![],
+            boolean: false,
+        };
+        plan.validate_schema(self.dataset)?;
+        Ok(plan)
+    }
+}
+
+/// Whether `a = b` connects `table` to the joined tables.
+fn connects(joined: &HashSet<String>, table: &str, a: &ColumnRef, b: &ColumnRef) -> bool {
+    (a.table == table && joined.contains(&b.table))
+        || (b.table == table && joined.contains(&a.table))
+}
+
+/// Orient the join edges as (column of the joined tables, column of `table`).
+fn join_keys(
+    joined: &HashSet<String>,
+    table: &str,
+    edges: Vec<Comparison>,
+) -> Result<Vec<(ColumnRef, ColumnRef)>> {
+    let mut keys = vec![];
+    for c in edges {
+        let (a, b) = c.as_join_edge().unwrap();
+        let (l, r) = if b.table == table { (a, b) } else { (b, a) };
+        ensure!(
+            r.table == table && joined.contains(&l.table),
+            "join condition {}.{} = {}.{} does not connect table {table} to the preceding tables",
+            l.table,
+            l.column,
+            r.table,
+            r.column
+        );
+        ensure!(
+            keys.iter().all(|(_, k)| k != r),
+            "column {}.{} is joined more than once",
+            r.table,
+            r.column
+        );
+        keys.push((l.clone(), r.clone()));
+    }
+    Ok(keys)
+}
+
+/// Track the names of the columns in the join result, following the naming of `alg::join`.
+struct Naming<'a> {
+    dataset: &'a DataSet,
+    joined: HashSet<String>,
+    names: HashMap<ColumnRef, String>,
+    columns: HashSet<String>,
+}
+
+impl<'a> Naming<'a> {
+    fn new(dataset: &'a DataSet, init_table: &str) -> Self {
+        let mut ans = Self {
+            dataset,
+            joined: HashSet::from([init_table.to_string()]),
+            names: HashMap::new(),
+            columns: HashSet::new(),
+        };
+        for column in dataset.tables[init_table].df.get_column_names() {
+            ans.insert(init_table, column, column.to_string());
+        }
+        ans
+    }
+
+    fn insert(&mut self, table: &str, column: &str, name: String) {
+        self.columns.insert(name.clone());
+        self.names.insert(
+            ColumnRef {
+                table: table.to_string(),
+                column: column.to_string(),
+            },
+            name,
+        );
+    }
+
+    fn name(&self, column: &ColumnRef) -> Result<String> {
+        self.names
+            .get(column)
+            .cloned()
+            .with_context(|| format!("column {}.{} is not joined", column.table, column.column))
+    }
+
+    fn join(
+        &mut self,
+        table: &str,
+        keys: Vec<(ColumnRef, ColumnRef)>,
+        join_type: JoinType,
+        is_assignment: bool,
+    ) -> Result<JoinStep> {
+        let left_join_keys = keys
+            .iter()
+            .map(|(l, _)| self.name(l))
+            .collect::<Result<Vec<_>>>()?;
+        let right_join_keys: Vec<String> = keys.iter().map(|(_, r)| r.column.clone()).collect();
+
+        for column in self.dataset.tables[table].df.get_column_names() {
+            let name = match right_join_keys.iter().position(|k| k == column) {
+                Some(i) => left_join_keys[i].clone(),
+                None if self.columns.contains(column) => format!("{column}:{table}"),
+                None => column.to_string(),
+            };
+            self.insert(table, column, name);
+        }
+        self.joined.insert(table.to_string());
+
+        if is_assignment {
+            for (l, r) in left_join_keys.iter().zip(right_join_keys.iter()) {
+                self.columns.remove(l);
+                self.columns.insert(r.clone());
+                for name in self.names.values_mut() {
+                    if name == l {
+                        *name = r.clone();
+                    }
+                }
+            }
+        }
+
+        Ok(JoinStep {
+            table_to_join: table.to_string(),
+            left_join_keys,
+            right_join_keys,
+            join_type,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fixture_dataset, game_dnfs};
+
+    #[test]
+    fn test_compile() {
+        let dataset = fixture_dataset();
+
+        let plan = compile(
+            "SELECT * FROM orders o, customer c WHERE c.c_custkey = o.o_custkey AND o_status = 'F'",
+            &dataset,
+        )
+        .unwrap();
+        assert_eq!(plan.init_table, "orders");
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].table_to_join, "customer");
+        assert_eq!(plan.steps[0].left_join_keys, vec!["o_custkey"]);
+        assert_eq!(plan.steps[0].right_join_keys, vec!["c_custkey"]);
+        assert_eq!(
+            plan.filters,
+            vec![Predicate::new(
+                "o_status",
+                CmpOp::Eq,
+                Operand::Str("F".to_string())
+            )]
+        );
+
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1 3", "2 5"]);
+
+        let plan = compile(
+            "SELECT * FROM customer JOIN orders ON c_custkey = o_custkey WHERE 11 <= o_orderkey",
+            &dataset,
+        )
+        .unwrap();
+        assert_eq!(
+            plan.filters,
+            vec![Predicate::new("o_orderkey", CmpOp::GtEq, Operand::Int(11))]
+        );
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1 3 + 1 4", "2 5"]);
+    }
+
+    #[test]
+    fn test_compile_error() {
+        let dataset = fixture_dataset();
+        let err = |sql| compile(sql, &dataset).unwrap_err().to_string();
+
+        assert!(err("SELECT * FROM custom").contains("cannot find table custom"));
+        assert!(
+            err("SELECT * FROM customer, orders WHERE c_custkey = o_custky")
+                .contains("cannot find column o_custky")
+        );
+        assert!(err("SELECT * FROM customer, orders").contains("cross products"));
+        assert!(err(
+            "SELECT * FROM customer, orders WHERE c_custkey = o_custkey OR o_orderkey = 1"
+        )
+        .contains("unsupported condition"));
+        assert!(err("SELECT *, c_name FROM customer").contains("cannot mix"));
+        assert!(err("SELECT c_nam FROM customer").contains("cannot find column c_nam"));
+    }
+
+    #[test]
+    fn test_compile_projection() {
+        let dataset = fixture_dataset();
+
+        let plan = compile(
+            "SELECT c.c_name FROM customer c JOIN orders o ON c.c_custkey = o.o_custkey",
+            &dataset,
+        )
+        .unwrap();
+        assert_eq!(plan.projection, vec!["c_name"]);
+
+        assert_eq!(game_dnfs(&dataset, &plan), vec!["1 3 + 1 4", "2 5"]);
+    }
+}
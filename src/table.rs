@@ -1,11 +1,23 @@
-use crate::{OwnerId, OwnerSet};
-use anyhow::{Context, Result};
+use crate::{tpch::TPCH_SCHEMAS, OwnerId, OwnerNames};
+use anyhow::{bail, ensure, Context, Result};
+use memmap2::Mmap;
 use polars::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+use std::{
+    collections::HashMap, fs::File, io::BufReader, ops::Deref, ops::Range, path::Path, sync::Arc,
+};
 
 pub const ROW_ID_COL_NAME: &str = "_row_id";
 
+/// File extensions of the owner assignment files of `(row_id, owner_id)` pairs.
+pub const OWNER_PAIRS_EXTENSIONS: &[&str] = &["csv", "parquet", "ipc", "arrow"];
+
+/// File extensions of the source tables that can be loaded.
+///
+/// `.tbl` files are pipe-delimited without header, as generated by TPC-H dbgen. They are read
+/// with the built-in schema of the TPC-H table of the same name.
+pub const TABLE_EXTENSIONS: &[&str] = &["csv", "parquet", "ipc", "arrow", "tbl"];
+
 #[derive(
     Debug,
     Default,
@@ -31,42 +43,234 @@ pub const ROW_ID_COL_NAME: &str = "_row_id";
 #[as_mut(forward)]
 pub struct RowId(pub u64);
 
+/// The owners of the rows of a table, in compressed sparse row form: the owners of row id `r`
+/// are `owners[offsets[r]..offsets[r + 1]]`, sorted and deduplicated. Row ids are dense, so
+/// this takes little more memory than the owner ids themselves.
+///
+/// Both arrays are either owned or borrowed from a memory-mapped file, see
+/// [`OwnerMap::from_mmap`].
+#[derive(Debug, Clone, Default)]
+pub struct OwnerMap {
+    offsets: Buffer<u64>,
+    owners: Buffer<OwnerId>,
+}
+
+/// An array owned or borrowed from the bytes `range` of a memory-mapped file.
+#[derive(Debug, Clone)]
+enum Buffer<T> {
+    Owned(Vec<T>),
+    Mapped {
+        mmap: Arc<Mmap>,
+        range: Range<usize>,
+    },
+}
+
+impl<T> Default for Buffer<T> {
+    fn default() -> Self {
+        Self::Owned(vec![])
+    }
+}
+
+impl<T: bytemuck::Pod> Deref for Buffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Owned(values) => values,
+            // the alignment is checked when mapped
+            Self::Mapped { mmap, range } => bytemuck::cast_slice(&mmap[range.clone()]),
+        }
+    }
+}
+
+impl PartialEq for OwnerMap {
+    fn eq(&self, other: &Self) -> bool {
+        *self.offsets == *other.offsets && *self.owners == *other.owners
+    }
+}
+
+impl Eq for OwnerMap {}
+
+impl OwnerMap {
+    /// Build an owner map from `(row_id, owner_id)` pairs of a table of `num_rows` rows. Row ids
+    /// at or beyond `num_rows` are rejected, since the offsets are sized by the largest one.
+    pub fn new(pairs: impl IntoIterator<Item = (RowId, OwnerId)>, num_rows: usize) -> Result<Self> {
+        let pairs: Vec<(RowId, OwnerId)> = pairs.into_iter().collect();
+        if let Some((row_id, _)) = pairs.iter().find(|(row_id, _)| row_id.0 >= num_rows as u64) {
+            bail!("row id {row_id} is out of range of {num_rows} rows");
+        }
+        Ok(pairs.into_iter().collect())
+    }
+
+    /// Build an owner map from its offsets and flat owner array, as returned by
+    /// [`OwnerMap::offsets`] and [`OwnerMap::owners`].
+    pub fn from_raw_parts(offsets: Vec<u64>, owners: Vec<OwnerId>) -> Result<Self> {
+        check_offsets(&offsets, owners.len())?;
+        Ok(Self {
+            offsets: Buffer::Owned(offsets),
+            owners: Buffer::Owned(owners),
+        })
+    }
+
+    /// Build an owner map whose offsets and flat owner array are the bytes `offsets` and
+    /// `owners` of a memory-mapped file, as native-endian u64 and u32, borrowed rather than
+    /// copied. The mapped file must not be modified while the owner map is alive.
+    pub fn from_mmap(mmap: Arc<Mmap>, offsets: Range<usize>, owners: Range<usize>) -> Result<Self> {
+        let offsets_slice: &[u64] = mmap
+            .get(offsets.clone())
+            .and_then(|bytes| bytemuck::try_cast_slice(bytes).ok())
+            .context("invalid owner map offsets")?;
+        let owners_slice: &[OwnerId] = mmap
+            .get(owners.clone())
+            .and_then(|bytes| bytemuck::try_cast_slice(bytes).ok())
+            .context("invalid owner map owners")?;
+        check_offsets(offsets_slice, owners_slice.len())?;
+        Ok(Self {
+            offsets: Buffer::Mapped {
+                mmap: mmap.clone(),
+                range: offsets,
+            },
+            owners: Buffer::Mapped {
+                mmap,
+                range: owners,
+            },
+        })
+    }
+
+    /// The owners of `row_id`, empty if it has none.
+    pub fn get(&self, row_id: RowId) -> &[OwnerId] {
+        let r = row_id.0 as usize;
+        match (self.offsets.get(r), self.offsets.get(r + 1)) {
+            (Some(&begin), Some(&end)) => &self.owners[begin as usize..end as usize],
+            _ => &[],
+        }
+    }
+
+    /// The row ids with owners and their owners, in the order of row ids.
+    pub fn iter(&self) -> impl Iterator<Item = (RowId, &[OwnerId])> + '_ {
+        self.offsets
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] < w[1])
+            .map(|(r, w)| (RowId(r as u64), &self.owners[w[0] as usize..w[1] as usize]))
+    }
+
+    /// All `(row_id, owner_id)` pairs, in the order of row ids.
+    pub fn pairs(&self) -> impl Iterator<Item = (RowId, OwnerId)> + '_ {
+        self.iter()
+            .flat_map(|(row_id, owners)| owners.iter().map(move |&owner| (row_id, owner)))
+    }
+
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// The owners of all rows, concatenated in the order of row ids.
+    pub fn owners(&self) -> &[OwnerId] {
+        &self.owners
+    }
+}
+
+impl FromIterator<(RowId, OwnerId)> for OwnerMap {
+    fn from_iter<T: IntoIterator<Item = (RowId, OwnerId)>>(iter: T) -> Self {
+        let mut pairs: Vec<(RowId, OwnerId)> = iter.into_iter().collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        let num_rows = pairs.last().map_or(0, |(row_id, _)| row_id.0 as usize + 1);
+        let mut offsets = vec![0; num_rows + 1];
+        for (row_id, _) in &pairs {
+            offsets[row_id.0 as usize + 1] += 1;
+        }
+        for r in 0..num_rows {
+            offsets[r + 1] += offsets[r];
+        }
+        Self {
+            offsets: Buffer::Owned(offsets),
+            owners: Buffer::Owned(pairs.into_iter().map(|(_, owner)| owner).collect()),
+        }
+    }
+}
+
+/// Check that `offsets` start at 0, never decrease and end at `num_owners`.
+fn check_offsets(offsets: &[u64], num_owners: usize) -> Result<()> {
+    ensure!(
+        offsets.first().is_none_or(|&first| first == 0)
+            && offsets.windows(2).all(|w| w[0] <= w[1])
+            && offsets.last().copied().unwrap_or_default() == num_owners as u64,
+        "invalid owner map offsets"
+    );
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct Table {
     pub name: String,
     pub df: DataFrame,
-    pub owner_map: HashMap<RowId, OwnerSet>,
+    pub owner_map: OwnerMap,
 }
 
 impl Table {
+    /// Load a table whose owner assignment is the pair of JSON files written by
+    /// `scripts/assign_data.py`. Owner ids, numbers or strings, are interned into `owner_names`.
     pub fn load(
         name: impl Into<String>,
-        csv_path: impl AsRef<Path>,
+        data_path: impl AsRef<Path>,
         row_id_path: impl AsRef<Path>,
         owner_path: impl AsRef<Path>,
+        owner_names: &mut OwnerNames,
     ) -> Result<Self> {
-        let mut df = CsvReader::new(File::open(csv_path)?).finish()?;
-        let row_id: Vec<u64> = serde_json::from_reader(BufReader::new(File::open(row_id_path)?))?;
+        let name = name.into();
+        let data_path = data_path.as_ref();
+        let row_id_path = row_id_path.as_ref();
+        let mut df = read_df(data_path)?;
+        let row_id = read_row_ids(row_id_path)?;
+        ensure!(
+            row_id.len() == df.height(),
+            "table {name}: {} has {} row ids but {} has {} rows",
+            row_id_path.display(),
+            row_id.len(),
+            data_path.display(),
+            df.height()
+        );
         df.with_column(Series::new(ROW_ID_COL_NAME, row_id))?;
 
-        #[derive(Debug, Deserialize)]
-        struct Owner {
-            index: HashMap<String, RowId>,
-            owner: HashMap<String, OwnerId>,
-        }
+        let owner_path = owner_path.as_ref();
+        let entries = read_owner_entries(owner_path).with_context(|| format!("table {name}"))?;
+        let owner_map = OwnerMap::new(
+            entries
+                .into_iter()
+                .map(|(i_v, s_v)| (i_v, owner_names.intern(&s_v))),
+            df.height(),
+        )
+        .with_context(|| format!("table {name}: invalid owners in {}", owner_path.display()))?;
 
-        let owner: Owner = serde_json::from_reader(BufReader::new(File::open(owner_path)?))?;
-        let mut owner_map: HashMap<RowId, OwnerSet> = HashMap::new();
-        for (i_k, i_v) in owner.index {
-            let s_v = *owner
-                .owner
-                .get(&i_k)
-                .context("failed to read -owner.json")?;
-            owner_map.entry(i_v).or_default().insert(s_v);
-        }
+        Ok(Self {
+            name,
+            df,
+            owner_map,
+        })
+    }
+
+    /// Load a table whose owner assignment is a CSV, Parquet or Arrow IPC file of
+    /// `(row_id, owner_id)` pairs. Row ids are the positions of the rows in the source file.
+    /// Owner ids are interned into `owner_names`, as in [`Table::load`].
+    pub fn load_with_owner_pairs(
+        name: impl Into<String>,
+        data_path: impl AsRef<Path>,
+        owner_path: impl AsRef<Path>,
+        owner_names: &mut OwnerNames,
+    ) -> Result<Self> {
+        let name = name.into();
+        let owner_path = owner_path.as_ref();
+        let mut df = read_df(data_path.as_ref())?;
+        let row_id: Vec<u64> = (0..df.height() as u64).collect();
+        df.with_column(Series::new(ROW_ID_COL_NAME, row_id))?;
+        let pairs = read_owner_pairs(owner_path, owner_names)?;
+        let owner_map = OwnerMap::new(pairs, df.height())
+            .with_context(|| format!("table {name}: invalid owners in {}", owner_path.display()))?;
 
         Ok(Self {
-            name: name.into(),
+            name,
             df,
             owner_map,
         })
@@ -74,14 +278,193 @@ impl Table {
 
     pub fn load_without_assignment(
         name: impl Into<String>,
-        csv_path: impl AsRef<Path>,
+        data_path: impl AsRef<Path>,
     ) -> Result<Self> {
-        let df = CsvReader::new(File::open(csv_path)?).finish()?;
+        let df = read_df(data_path.as_ref())?;
 
         Ok(Self {
             name: name.into(),
             df,
-            owner_map: HashMap::default(),
+            owner_map: OwnerMap::default(),
+        })
+    }
+}
+
+/// Read the row id of every row of a table from the JSON index file written by
+/// `scripts/assign_data.py`.
+pub(crate) fn read_row_ids(path: &Path) -> Result<Vec<u64>> {
+    serde_json::from_reader(BufReader::new(
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?,
+    ))
+    .with_context(|| format!("failed to read row ids from {}", path.display()))
+}
+
+/// Read the `(row_id, owner_id)` entries of a table from the JSON owner file written by
+/// `scripts/assign_data.py`, sorted so that owner ids are interned in a deterministic order.
+/// Owner ids, numbers or strings, are read as strings.
+pub(crate) fn read_owner_entries(path: &Path) -> Result<Vec<(RowId, String)>> {
+    #[derive(Debug, Deserialize)]
+    #[serde(untagged)]
+    enum RawOwnerId {
+        Int(u64),
+        Str(String),
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Owner {
+        index: HashMap<String, RowId>,
+        owner: HashMap<String, RawOwnerId>,
+    }
+
+    let owner: Owner = serde_json::from_reader(BufReader::new(
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?,
+    ))
+    .with_context(|| format!("failed to read owners from {}", path.display()))?;
+    let mut entries = owner
+        .index
+        .iter()
+        .map(|(i_k, &i_v)| {
+            let s_v = match owner.owner.get(i_k) {
+                Some(RawOwnerId::Int(id)) => id.to_string(),
+                Some(RawOwnerId::Str(name)) => name.clone(),
+                None => bail!("entry {i_k} of {} has no owner", path.display()),
+            };
+            Ok((i_v, s_v))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort_unstable();
+    Ok(entries)
+}
+
+/// Read a data frame from a CSV, Parquet, Arrow IPC or TPC-H `.tbl` file, chosen by the file
+/// extension.
+pub(crate) fn read_df(path: &Path) -> Result<DataFrame> {
+    let df = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tbl") => {
+            let name = path.file_stem().unwrap().to_string_lossy();
+            let schema = TPCH_SCHEMAS
+                .get(name.as_ref())
+                .with_context(|| format!("no built-in schema for {}", path.display()))?;
+            read_tbl(path, schema)?
+        }
+        ext => {
+            let f =
+                File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+            match ext {
+                Some("csv") => CsvReader::new(f).finish()?,
+                Some("parquet") => ParquetReader::new(f).finish()?,
+                Some("ipc" | "arrow") => IpcReader::new(f).finish()?,
+                _ => bail!("unsupported table format: {}", path.display()),
+            }
+        }
+    };
+    Ok(df)
+}
+
+/// Read the `row_id` and `owner_id` columns of an owner assignment file, interning owner ids
+/// into `owner_names`. Owner ids are either integers or strings.
+pub(crate) fn read_owner_pairs(
+    path: &Path,
+    owner_names: &mut OwnerNames,
+) -> Result<Vec<(RowId, OwnerId)>> {
+    let df = read_df(path)?;
+    let column = |name: &str, dtype: DataType| -> Result<Series> {
+        let column = df
+            .column(name)
+            .with_context(|| format!("cannot find column {name} in {}", path.display()))?;
+        Ok(column.cast(&dtype)?)
+    };
+    let row_ids = column("row_id", DataType::UInt64)?;
+    let owner_ids = match df.column("owner_id") {
+        Ok(column) if column.dtype() == &DataType::Utf8 => column.clone(),
+        _ => column("owner_id", DataType::UInt32)?.cast(&DataType::Utf8)?,
+    };
+
+    row_ids
+        .u64()?
+        .into_iter()
+        .zip(owner_ids.utf8()?)
+        .enumerate()
+        .map(|(i, (row_id, owner_id))| {
+            let (row_id, owner_id) = row_id.zip(owner_id).with_context(|| {
+                format!("null row id or owner id at row {i} in {}", path.display())
+            })?;
+            Ok((RowId(row_id), owner_names.intern(owner_id)))
         })
+        .collect()
+}
+
+/// Read a pipe-delimited file without header, such as the `.tbl` files of TPC-H dbgen, with
+/// the column names and dtypes of `schema`. Trailing columns beyond the schema, e.g. the empty
+/// one after the terminating `|`, are dropped.
+pub fn read_tbl(path: impl AsRef<Path>, schema: &Schema) -> Result<DataFrame> {
+    let path = path.as_ref();
+    let f = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let dtypes: Vec<DataType> = schema.iter_dtypes().cloned().collect();
+    let mut df = CsvReader::new(f)
+        .has_header(false)
+        .with_delimiter(b'|')
+        .with_quote_char(None)
+        .with_dtypes_slice(Some(&dtypes))
+        .with_projection(Some((0..schema.len()).collect()))
+        .finish()
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    df.set_column_names(&schema.iter_names().collect::<Vec<_>>())?;
+    Ok(df)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_owner_map() {
+        let owner_map: OwnerMap = [(3, 2), (0, 1), (3, 1), (0, 1), (1, 4)]
+            .into_iter()
+            .map(|(r, o)| (RowId(r), OwnerId(o)))
+            .collect();
+        assert_eq!(owner_map.offsets(), [0, 1, 2, 2, 4]);
+        assert_eq!(owner_map.get(RowId(0)), [OwnerId(1)]);
+        assert_eq!(owner_map.get(RowId(3)), [OwnerId(1), OwnerId(2)]);
+        assert!(owner_map.get(RowId(2)).is_empty());
+        assert!(owner_map.get(RowId(9)).is_empty());
+        assert_eq!(
+            owner_map
+                .iter()
+                .map(|(r, o)| (r.0, o.len()))
+                .collect::<Vec<_>>(),
+            [(0, 1), (1, 1), (3, 2)]
+        );
+        assert_eq!(owner_map.pairs().count(), 4);
+
+        let raw =
+            OwnerMap::from_raw_parts(owner_map.offsets().to_vec(), owner_map.owners().to_vec())
+                .unwrap();
+        assert_eq!(raw, owner_map);
+        assert!(OwnerMap::from_raw_parts(vec![0, 2, 1], vec![OwnerId(1)]).is_err());
+
+        // borrowed from a mapped file of the offsets then the owners
+        let mut bytes: Vec<u8> = bytemuck::cast_slice(owner_map.offsets()).to_vec();
+        bytes.extend_from_slice(bytemuck::cast_slice(owner_map.owners()));
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&bytes).unwrap();
+        let mmap = Arc::new(unsafe { Mmap::map(&f).unwrap() });
+        let mapped = OwnerMap::from_mmap(mmap.clone(), 0..40, 40..56).unwrap();
+        assert_eq!(mapped, owner_map);
+        assert_eq!(mapped.clone().get(RowId(3)), [OwnerId(1), OwnerId(2)]);
+        assert!(OwnerMap::from_mmap(mmap.clone(), 0..40, 40..60).is_err());
+        assert!(OwnerMap::from_mmap(mmap, 4..40, 40..56).is_err());
+
+        // row ids are bounded by the number of rows of the table
+        let pairs: Vec<_> = owner_map.pairs().collect();
+        assert_eq!(OwnerMap::new(pairs.clone(), 4).unwrap(), owner_map);
+        let err =
+            OwnerMap::new(pairs.into_iter().chain([(RowId(1 << 40), OwnerId(1))]), 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row id 1099511627776 is out of range of 4 rows"
+        );
+        assert_eq!(OwnerMap::default().iter().count(), 0);
     }
 }
@@ -1,5 +1,11 @@
 use super::*;
 use once_cell::sync::Lazy;
+use polars::prelude::*;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
 
 static FIXTURE_GAME: Lazy<Game> = Lazy::new(|| {
     let exp = dnf!(1 2 4 + 1 2 5 + 2 3 4 + 2 3 5 + 4 5);
@@ -37,3 +43,149 @@ pub(crate) fn assert_f64_eq(expect: f64, actual: f64) {
         panic!("assert failed. expect: {expect}, actual: {actual}.");
     }
 }
+
+/// A fresh temporary directory, removed when dropped, with empty `data` and `assignment`
+/// subdirectories for the source tables and the owner assignment of a dataset.
+pub(crate) fn dataset_dirs() -> (TempDir, PathBuf, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().join("data");
+    let meta_dir = dir.path().join("assignment");
+    fs::create_dir_all(&data_dir).unwrap();
+    fs::create_dir_all(&meta_dir).unwrap();
+    (dir, data_dir, meta_dir)
+}
+
+pub(crate) fn write_csv(path: impl AsRef<Path>, df: &mut DataFrame) {
+    CsvWriter::new(File::create(path).unwrap())
+        .finish(df)
+        .unwrap();
+}
+
+/// The DNFs of `games`, sorted so that they do not depend on the order of the games.
+pub(crate) fn sorted_dnfs(games: &[Game]) -> Vec<String> {
+    let mut dnfs: Vec<_> = games.iter().map(|g| g.dnf().to_string()).collect();
+    dnfs.sort();
+    dnfs
+}
+
+/// The sorted DNFs of the games of `plan`, see [`sorted_dnfs`].
+pub(crate) fn game_dnfs(dataset: &DataSet, plan: &JoinPlan) -> Vec<String> {
+    sorted_dnfs(&Game::generate_games(dataset, plan).unwrap())
+}
+
+/// A tiny dataset with owner assignment: customers and their orders, including a customer
+/// without order and an order without customer.
+pub(crate) fn fixture_dataset() -> DataSet {
+    fn table(name: &str, mut df: DataFrame, owners: &[&[u32]]) -> Table {
+        let row_id: Vec<u64> = (0..df.height() as u64).collect();
+        df.with_column(Series::new(ROW_ID_COL_NAME, row_id))
+            .unwrap();
+        let owner_map = owners
+            .iter()
+            .enumerate()
+            .flat_map(|(i, o)| o.iter().map(move |&o| (RowId(i as u64), OwnerId(o))))
+            .collect();
+        Table {
+            name: name.to_string(),
+            df,
+            owner_map,
+        }
+    }
+
+    let customer = table(
+        "customer",
+        df!(
+            "c_custkey" => &[1i64, 2, 3],
+            "c_name" => &["alice", "bob", "carol"]
+        )
+        .unwrap(),
+        &[&[1], &[2], &[6]],
+    );
+    let orders = table(
+        "orders",
+        df!(
+            "o_orderkey" => &[10i64, 11, 12, 13],
+            "o_custkey" => &[1i64, 1, 2, 9],
+            "o_status" => &["F", "O", "F", "O"]
+        )
+        .unwrap(),
+        &[&[3], &[3, 4], &[5], &[7]],
+    );
+
+    let tables = HashMap::from([
+        (customer.name.clone(), customer),
+        (orders.name.clone(), orders),
+    ]);
+    let owner_set = tables
+        .values()
+        .flat_map(|t| t.owner_map.owners().iter().copied())
+        .collect();
+    DataSet {
+        name: "fixture".to_string(),
+        tables,
+        owner_set,
+        owner_names: OwnerNames::default(),
+    }
+}
+
+/// A tiny soccer dataset without owner assignment: teams are identified by `*_api_id` columns.
+pub(crate) fn fixture_soccer_dataset() -> DataSet {
+    fn team() -> DataFrame {
+        df!(
+            "id" => &[1i64, 2, 3],
+            "team_api_id" => &[10i64, 20, 30],
+            "team_long_name" => &["Ajax", "Benfica", "Celtic"]
+        )
+        .unwrap()
+    }
+
+    let frames = [
+        (
+            "Match",
+            df!(
+                "id" => &[1i64, 2, 3, 4],
+                "country_id" => &[1i64, 1, 1, 1],
+                "league_id" => &[1i64, 1, 1, 1],
+                "season" => &["2015/2016", "2015/2016", "2015/2016", "2014/2015"],
+                "home_team_api_id" => &[10i64, 20, 10, 30],
+                "away_team_api_id" => &[20i64, 30, 30, 10],
+                "home_team_goal" => &[2i64, 0, 1, 3],
+                "away_team_goal" => &[1i64, 1, 1, 0]
+            )
+            .unwrap(),
+        ),
+        (
+            "Country",
+            df!("id" => &[1i64], "name" => &["Europe"]).unwrap(),
+        ),
+        ("HomeTeam", team()),
+        ("AwayTeam", team()),
+        (
+            "League",
+            df!(
+                "id" => &[1i64],
+                "country_id" => &[1i64],
+                "name" => &["Champions League"]
+            )
+            .unwrap(),
+        ),
+    ];
+
+    let tables = frames
+        .into_iter()
+        .map(|(name, df)| {
+            let table = Table {
+                name: name.to_string(),
+                df,
+                owner_map: OwnerMap::default(),
+            };
+            (table.name.clone(), table)
+        })
+        .collect();
+    DataSet {
+        name: "soccer".to_string(),
+        tables,
+        owner_set: OwnerSet::default(),
+        owner_names: OwnerNames::default(),
+    }
+}
@@ -0,0 +1,156 @@
+//! Built-in schemas of the TPC-H tables, used to read the raw `.tbl` files generated by dbgen.
+//!
+//! Column names follow `scripts/transform_data.py`, so that tables read from `.tbl` files and
+//! from the transformed CSVs are interchangeable in join plans.
+
+use once_cell::sync::Lazy;
+use polars::prelude::{DataType, Schema};
+use std::collections::HashMap;
+
+pub static TPCH_SCHEMAS: Lazy<HashMap<&'static str, Schema>> = Lazy::new(|| {
+    use DataType::{Float64 as F, Int64 as I, Utf8 as S};
+
+    let tables: [(&str, &[(&str, DataType)]); 8] = [
+        (
+            "region",
+            &[("r_regionkey", I), ("r_name", S), ("r_comment", S)],
+        ),
+        (
+            "nation",
+            &[
+                ("n_nationkey", I),
+                ("n_name", S),
+                ("n_regionkey", I),
+                ("n_comment", S),
+            ],
+        ),
+        (
+            "supplier",
+            &[
+                ("s_suppkey", I),
+                ("s_name", S),
+                ("s_address", S),
+                ("s_nationkey", I),
+                ("s_phone", S),
+                ("s_acctbal", F),
+                ("s_comment", S),
+            ],
+        ),
+        (
+            "customer",
+            &[
+                ("c_custkey", I),
+                ("c_name", S),
+                ("c_address", S),
+                ("c_nationkey", I),
+                ("c_phone", S),
+                ("c_acctbal", F),
+                ("c_mktsegment", S),
+                ("c_comment", S),
+            ],
+        ),
+        (
+            "part",
+            &[
+                ("p_partkey", I),
+                ("p_name", S),
+                ("p_mfgr", S),
+                ("p_brand", S),
+                ("p_type", S),
+                ("p_size", I),
+                ("p_container", S),
+                ("p_retailprice", F),
+                ("p_comment", S),
+            ],
+        ),
+        (
+            "partsupp",
+            &[
+                ("ps_partkey", I),
+                ("ps_suppkey", I),
+                ("ps_availqty", I),
+                ("ps_supplycost", F),
+                ("ps_comment", S),
+            ],
+        ),
+        (
+            "orders",
+            &[
+                ("o_orderkey", I),
+                ("o_custkey", I),
+                ("o_orderstatus", S),
+                ("o_totalprice", F),
+                ("o_orderdata", S),
+                ("o_orderpriority", S),
+                ("o_clerk", S),
+                ("o_shippriority", I),
+                ("o_comment", S),
+            ],
+        ),
+        (
+            "lineitem",
+            &[
+                ("l_orderkey", I),
+                ("l_partkey", I),
+                ("l_suppkey", I),
+                ("l_linenumber", I),
+                ("l_quantity", F),
+                ("l_extendedprice", F),
+                ("l_discount", F),
+                ("l_tax", F),
+                ("l_returnflag", S),
+                ("l_linestatus", S),
+                ("l_shipdate", S),
+                ("l_commitdata", S),
+                ("l_receiptdata", S),
+                ("l_shipinstruct", S),
+                ("l_shipmode", S),
+                ("l_comment", S),
+            ],
+        ),
+    ];
+
+    tables
+        .into_iter()
+        .map(|(name, columns)| {
+            let schema = columns
+                .iter()
+                .map(|(column, dtype)| polars::prelude::Field::new(column, dtype.clone()))
+                .collect();
+            (name, schema)
+        })
+        .collect()
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Table;
+    use polars::prelude::AnyValue;
+    use std::fs;
+
+    #[test]
+    fn test_load_tbl() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // as generated by dbgen, and with the row number appended by scripts/tpch-dbgen.sh
+        let path = dir.path().join("nation.tbl");
+        for content in [
+            "0|ALGERIA|0| haggle. carefully final|\n1|ARGENTINA|1|al foxes promise|\n",
+            "0|ALGERIA|0| haggle. carefully final||nation.1\n1|ARGENTINA|1|al foxes promise||nation.2\n",
+        ] {
+            fs::write(&path, content).unwrap();
+            let table = Table::load_without_assignment("nation", &path).unwrap();
+            assert_eq!(table.df.schema(), TPCH_SCHEMAS["nation"]);
+            assert_eq!(table.df.height(), 2);
+            assert_eq!(
+                table.df.column("n_name").unwrap().get(1),
+                AnyValue::Utf8("ARGENTINA")
+            );
+        }
+
+        let path = dir.path().join("unknown.tbl");
+        fs::write(&path, "0|a|\n").unwrap();
+        assert!(Table::load_without_assignment("unknown", &path).is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{Dnf, OwnerId, OwnerSet};
+use crate::{
+    dnf::{recursive_decompose, unionfind::UnionFind, Implicant, RecursiveDecompose},
+    Dnf, OwnerId, OwnerSet,
+};
 use anyhow::{Error, Result};
 use ref_cast::RefCast;
 #[cfg(test)]
@@ -98,12 +101,12 @@ pub fn dnf_to_syns_with_filter(
         .collect()
 }
 
-pub fn cartesian_product(owner_sets: &[OwnerSet]) -> Vec<OwnerSet> {
+pub fn cartesian_product(owner_sets: &[&[OwnerId]]) -> Vec<OwnerSet> {
     match owner_sets.split_first() {
         Some((first, rest)) => {
             let init: Vec<OwnerSet> = first.iter().cloned().map(|n| OwnerSet::from([n])).collect();
 
-            rest.iter().cloned().fold(init, partial_cartesian)
+            rest.iter().copied().fold(init, partial_cartesian)
         }
         None => {
             vec![]
@@ -111,7 +114,7 @@ pub fn cartesian_product(owner_sets: &[OwnerSet]) -> Vec<OwnerSet> {
     }
 }
 
-pub fn partial_cartesian(a: Vec<OwnerSet>, b: OwnerSet) -> Vec<OwnerSet> {
+pub fn partial_cartesian(a: Vec<OwnerSet>, b: &[OwnerId]) -> Vec<OwnerSet> {
     a.into_iter()
         .flat_map(|xs| {
             b.iter()
@@ -126,6 +129,59 @@ pub fn partial_cartesian(a: Vec<OwnerSet>, b: OwnerSet) -> Vec<OwnerSet> {
         .collect()
 }
 
+/// The decomposition of the AND of the ORs of `owner_sets`, i.e. of the DNF of their
+/// [`cartesian_product`], built without expanding it.
+///
+/// Owner sets sharing owners are merged, and only their product is expanded and decomposed;
+/// the others are disjoint, so each is an OR node of the AND. `owner_sets` must not be empty,
+/// nor contain an empty set.
+pub fn factored_product(owner_sets: &[&[OwnerId]]) -> RecursiveDecompose<OwnerId> {
+    let mut union = UnionFind::new(owner_sets.len());
+    let mut first_set: HashMap<OwnerId, usize> = HashMap::new();
+    for (i, owners) in owner_sets.iter().enumerate() {
+        for &owner in owners.iter() {
+            union.union(*first_set.entry(owner).or_insert(i), i);
+        }
+    }
+    let mut components: HashMap<usize, Vec<&[OwnerId]>> = HashMap::new();
+    for (i, label) in union.into_labeling().into_iter().enumerate() {
+        components.entry(label).or_default().push(owner_sets[i]);
+    }
+
+    let mut children = Vec::with_capacity(components.len());
+    for (_, component) in components {
+        let child = match component[..] {
+            [[owner]] => RecursiveDecompose::Var(*owner),
+            [owners] => RecursiveDecompose::Or(
+                owners
+                    .iter()
+                    .copied()
+                    .map(RecursiveDecompose::Var)
+                    .collect(),
+            ),
+            _ => {
+                let mut exp: Dnf<OwnerId> = cartesian_product(&component)
+                    .into_iter()
+                    .map(|owners| Implicant::from(owners.0))
+                    .collect();
+                // owners may be absorbed, e.g. 4 in the product of {1} and {1, 4}
+                exp.minimize();
+                recursive_decompose(&exp, &exp.all_variables())
+            }
+        };
+        // flatten nested ANDs
+        match child {
+            RecursiveDecompose::And(list) => children.extend(list),
+            child => children.push(child),
+        }
+    }
+    if children.len() == 1 {
+        children.pop().unwrap()
+    } else {
+        RecursiveDecompose::And(children)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,11 +197,46 @@ mod tests {
 
     #[test]
     fn test_cartesian_product() {
-        let a = OwnerSet::from_iter([1, 2, 3]);
-        let b = OwnerSet::from_iter([4, 5]);
-        let c = OwnerSet::from_iter([6, 7, 8]);
+        let a = [1, 2, 3].map(OwnerId);
+        let b = [4, 5].map(OwnerId);
+        let c = [6, 7, 8].map(OwnerId);
 
-        let products = cartesian_product(&vec![a, b, c]);
+        let products = cartesian_product(&[&a, &b, &c]);
         dbg!(&products);
     }
+
+    #[test]
+    fn test_factored_product() {
+        let owners = |ids: &[u32]| ids.iter().copied().map(OwnerId).collect::<Vec<_>>();
+        let (a, b, c, e) = (
+            owners(&[1, 2, 3]),
+            owners(&[4]),
+            owners(&[3, 5]),
+            owners(&[6, 7]),
+        );
+
+        let var = |id| RecursiveDecompose::Var(OwnerId(id));
+        let d = factored_product(&[&a, &b, &e]);
+        assert_eq!(
+            d,
+            RecursiveDecompose::And(vec![
+                RecursiveDecompose::Or(vec![var(1), var(2), var(3)]),
+                var(4),
+                RecursiveDecompose::Or(vec![var(6), var(7)]),
+            ])
+        );
+        assert_eq!(factored_product(&[&b, &b]), var(4));
+        assert_eq!(factored_product(&[&b, &owners(&[4, 9])]), var(4));
+
+        for owner_sets in [vec![&a[..], &b, &c], vec![&a, &c, &a], vec![&c]] {
+            let mut exp: Dnf<OwnerId> = cartesian_product(&owner_sets)
+                .into_iter()
+                .map(|owners| Implicant::from(owners.0))
+                .collect();
+            exp.minimize();
+            let d = factored_product(&owner_sets);
+            assert_eq!(d, recursive_decompose(&exp, &exp.all_variables()));
+            assert_eq!(d.expand(), exp);
+        }
+    }
 }
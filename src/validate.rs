@@ -0,0 +1,381 @@
+use crate::{
+    alg::join::join_schema,
+    dataset::{owner_pairs_file, table_files},
+    table::{read_df, read_owner_entries, read_owner_pairs, read_row_ids},
+    DataSet, JoinPlan, OwnerNames, Plan, RowId, Table, ROW_ID_COL_NAME,
+};
+use anyhow::Result;
+use polars::prelude::*;
+use std::{collections::HashMap, fmt, path::Path};
+
+/// An inconsistency between a dataset and its plan or owner assignment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// The table at fault, if any.
+    pub table: Option<String>,
+    /// The row at fault, as its position in the table.
+    pub row: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.table, self.row) {
+            (Some(table), Some(row)) => write!(f, "table {table}, row {row}: {}", self.message),
+            (Some(table), None) => write!(f, "table {table}: {}", self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// All inconsistencies found by [`DataSet::validate`].
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn push(&mut self, table: Option<&str>, row: Option<usize>, message: impl Into<String>) {
+        self.issues.push(Issue {
+            table: table.map(str::to_string),
+            row,
+            message: message.into(),
+        });
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl DataSet {
+    /// Check the owner assignment files in `meta_dir` against the source tables in `csv_dir`
+    /// before the dataset is loaded, since [`DataSet::load`] stops at the first table whose row
+    /// ids do not fit its rows. Report, for every table, unreadable source or assignment files,
+    /// a number of row ids of the JSON format other than its number of rows, and owner entries
+    /// of row ids beyond its rows.
+    pub fn validate_files(csv_dir: &Path, meta_dir: &Path) -> Result<ValidationReport> {
+        let mut report = ValidationReport::default();
+        for (name, data_f) in table_files(csv_dir)? {
+            if let Err(e) = validate_table_files(&name, &data_f, meta_dir, &mut report) {
+                report.push(Some(&name), None, format!("{e:#}"));
+            }
+        }
+        Ok(report)
+    }
+
+    /// Check the dataset against `plan` before any game is generated, and report every
+    /// inconsistency: plan errors, and for the tables of the plan, null or duplicated row ids,
+    /// rows without owner and owner entries of unknown row ids.
+    pub fn validate(&self, plan: &Plan) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        if let Err(e) = plan.validate_schema(self) {
+            report.push(None, None, format!("invalid plan: {e:#}"));
+        }
+
+        let plans: Vec<JoinPlan> = match plan {
+            Plan::Union(plan) => plan.branches().collect(),
+            Plan::Join(plan) => vec![plan.clone()],
+        };
+        if self.owner_set.is_empty() {
+            for plan in &plans {
+                self.validate_owner_columns(plan, &mut report);
+            }
+        } else {
+            let mut tables: Vec<&str> = plans.iter().flat_map(|plan| plan.tables()).collect();
+            tables.sort_unstable();
+            tables.dedup();
+            for table in tables.into_iter().filter_map(|name| self.tables.get(name)) {
+                table.validate_assignment(&mut report);
+            }
+        }
+        report
+    }
+
+    /// Without owner assignment, owners are read from the owner columns of the join result,
+    /// which must be integers.
+    fn validate_owner_columns(&self, plan: &JoinPlan, report: &mut ValidationReport) {
+        if plan.owner_columns.is_empty() {
+            report.push(
+                None,
+                None,
+                format!(
+                    "dataset {} has no owner assignment and the join plan declares no owner columns",
+                    self.name
+                ),
+            );
+            return;
+        }
+        let Ok(schema) = join_schema(|name| self.tables.get(name).map(|t| &t.df), plan, false)
+        else {
+            // already reported as an invalid plan
+            return;
+        };
+        let Ok(columns) = plan.match_owner_columns(schema.iter_names().map(|c| c.as_str())) else {
+            return;
+        };
+        for column in columns {
+            let dtype = schema.get(column).unwrap();
+            if !matches!(
+                dtype,
+                DataType::UInt32 | DataType::UInt64 | DataType::Int32 | DataType::Int64
+            ) {
+                report.push(
+                    None,
+                    None,
+                    format!("owner column {column} has dtype {dtype}, but owner ids are integers"),
+                );
+            }
+        }
+    }
+}
+
+/// Check the assignment files of table `name` read from `data_f`, pushing mismatches to
+/// `report`. Unreadable files are returned as errors, reported by the caller.
+fn validate_table_files(
+    name: &str,
+    data_f: &Path,
+    meta_dir: &Path,
+    report: &mut ValidationReport,
+) -> Result<()> {
+    let table = Some(name);
+    let num_rows = read_df(data_f)?.height();
+    let (owner_f, row_ids) = match owner_pairs_file(meta_dir, name) {
+        Some(owner_f) => {
+            let pairs = read_owner_pairs(&owner_f, &mut OwnerNames::default())?;
+            let row_ids: Vec<RowId> = pairs.into_iter().map(|(row_id, _)| row_id).collect();
+            (owner_f, row_ids)
+        }
+        None => {
+            let row_id_f = meta_dir.join(format!("{name}-index.json"));
+            let num_row_ids = read_row_ids(&row_id_f)?.len();
+            if num_row_ids != num_rows {
+                report.push(
+                    table,
+                    None,
+                    format!(
+                        "{} has {num_row_ids} row ids but {} has {num_rows} rows",
+                        row_id_f.display(),
+                        data_f.display()
+                    ),
+                );
+            }
+            let owner_f = meta_dir.join(format!("{name}-owner.json"));
+            let entries = read_owner_entries(&owner_f)?;
+            let row_ids = entries.into_iter().map(|(row_id, _)| row_id).collect();
+            (owner_f, row_ids)
+        }
+    };
+
+    let mut beyond: Vec<RowId> = row_ids
+        .into_iter()
+        .filter(|row_id| row_id.0 >= num_rows as u64)
+        .collect();
+    beyond.sort_unstable();
+    beyond.dedup();
+    for row_id in beyond {
+        report.push(
+            table,
+            None,
+            format!(
+                "owner entry of row id {row_id} in {} beyond the {num_rows} rows of {}",
+                owner_f.display(),
+                data_f.display()
+            ),
+        );
+    }
+    Ok(())
+}
+
+impl Table {
+    fn validate_assignment(&self, report: &mut ValidationReport) {
+        let table = Some(self.name.as_str());
+        let row_ids = match self.df.column(ROW_ID_COL_NAME).and_then(|c| c.u64()) {
+            Ok(row_ids) => row_ids,
+            Err(e) => {
+                report.push(table, None, format!("invalid row id column: {e}"));
+                return;
+            }
+        };
+
+        let mut rows: HashMap<u64, usize> = HashMap::with_capacity(row_ids.len());
+        for (i, row_id) in row_ids.into_iter().enumerate() {
+            let Some(row_id) = row_id else {
+                report.push(table, Some(i), "null row id");
+                continue;
+            };
+            if let Some(j) = rows.insert(row_id, i) {
+                report.push(
+                    table,
+                    Some(i),
+                    format!("row id {row_id} duplicates row {j}"),
+                );
+            }
+            if self.owner_map.get(RowId(row_id)).is_empty() {
+                report.push(table, Some(i), format!("row id {row_id} has no owner"));
+            }
+        }
+
+        let unknown = self
+            .owner_map
+            .iter()
+            .map(|(row_id, _)| row_id.0)
+            .filter(|row_id| !rows.contains_key(row_id));
+        for row_id in unknown {
+            report.push(
+                table,
+                None,
+                format!("owner entry of unknown row id {row_id}"),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{dataset_dirs, fixture_dataset, fixture_soccer_dataset},
+        OwnerId, PLANS,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_validate() {
+        let plan = Plan::Join(
+            JoinPlan::builder()
+                .init_table("customer")
+                .join("orders", ["c_custkey"], ["o_custkey"], JoinType::Inner)
+                .build()
+                .unwrap(),
+        );
+        let mut dataset = fixture_dataset();
+        assert!(dataset.validate(&plan).is_ok());
+
+        let orders = dataset.tables.get_mut("orders").unwrap();
+        orders.owner_map = orders
+            .owner_map
+            .pairs()
+            .filter(|(row_id, _)| row_id.0 != 1)
+            .chain([(RowId(42), OwnerId(8))])
+            .collect();
+        let report = dataset.validate(&plan);
+        let issues: Vec<String> = report.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "table orders, row 1: row id 1 has no owner",
+                "table orders: owner entry of unknown row id 42",
+            ]
+        );
+
+        let customer = dataset.tables.get_mut("customer").unwrap();
+        customer
+            .df
+            .with_column(Series::new(ROW_ID_COL_NAME, [0u64, 1, 1]))
+            .unwrap();
+        let report = dataset.validate(&plan);
+        assert_eq!(report.issues.len(), 4);
+        assert_eq!(
+            report.issues[0].to_string(),
+            "table customer, row 2: row id 1 duplicates row 1"
+        );
+
+        let plan = Plan::Join(JoinPlan::builder().init_table("nation").build().unwrap());
+        let report = fixture_dataset().validate(&plan);
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.issues[0].message.starts_with("invalid plan"));
+    }
+
+    #[test]
+    fn test_validate_files() {
+        let (_dir, data_dir, meta_dir) = dataset_dirs();
+        for name in ["t1", "t2"] {
+            fs::write(data_dir.join(format!("{name}.csv")), "k\n1\n2\n").unwrap();
+        }
+        fs::write(
+            meta_dir.join("t1-owner.csv"),
+            "row_id,owner_id\n0,1\n1,2\n5,3\n",
+        )
+        .unwrap();
+        fs::write(meta_dir.join("t2-index.json"), "[0, 1, 2]").unwrap();
+        fs::write(
+            meta_dir.join("t2-owner.json"),
+            r#"{ "index": { "0": 0, "1": 1 }, "owner": { "0": 1, "1": 2 } }"#,
+        )
+        .unwrap();
+
+        // every mismatch is reported, while loading stops at the first one
+        assert!(DataSet::load("test", &data_dir, &Some(meta_dir.clone())).is_err());
+        let report = DataSet::validate_files(&data_dir, &meta_dir).unwrap();
+        let issues: Vec<&str> = report.issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(report.issues[0].table.as_deref(), Some("t1"));
+        assert!(issues[0].starts_with("owner entry of row id 5 in"));
+        assert_eq!(report.issues[1].table.as_deref(), Some("t2"));
+        assert!(issues[1].ends_with("t2.csv has 2 rows"));
+
+        fs::write(meta_dir.join("t1-owner.csv"), "row_id,owner_id\n0,1\n1,2\n").unwrap();
+        fs::write(meta_dir.join("t2-index.json"), "[0, 1]").unwrap();
+        assert!(DataSet::validate_files(&data_dir, &meta_dir)
+            .unwrap()
+            .is_ok());
+        assert!(DataSet::load("test", &data_dir, &Some(meta_dir.clone())).is_ok());
+
+        // unreadable assignment files are reported with the other tables
+        fs::write(data_dir.join("t3.csv"), "k\n1\n").unwrap();
+        fs::write(meta_dir.join("t2-index.json"), "[0, 1, 2]").unwrap();
+        fs::write(meta_dir.join("t3-index.json"), "[0]").unwrap();
+        fs::write(
+            meta_dir.join("t3-owner.json"),
+            r#"{ "index": { "0": 0 }, "owner": {} }"#,
+        )
+        .unwrap();
+        let report = DataSet::validate_files(&data_dir, &meta_dir).unwrap();
+        let issues: Vec<String> = report.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].starts_with("table t2: ") && issues[0].ends_with("t2.csv has 2 rows"));
+        assert!(issues[1].starts_with("table t3: entry 0 of "));
+        assert!(issues[1].ends_with("t3-owner.json has no owner"));
+
+        fs::remove_file(meta_dir.join("t3-index.json")).unwrap();
+        let report = DataSet::validate_files(&data_dir, &meta_dir).unwrap();
+        assert_eq!(report.issues.len(), 2);
+        assert!(report.issues[1].message.starts_with("failed to open"));
+    }
+
+    #[test]
+    fn test_validate_owner_columns() {
+        let dataset = fixture_soccer_dataset();
+        let mut plan = PLANS["soccer"].clone();
+        assert!(dataset.validate(&Plan::Join(plan.clone())).is_ok());
+
+        plan.owner_columns = vec!["team_long_name".to_string()];
+        let issues: Vec<String> = dataset
+            .validate(&Plan::Join(plan.clone()))
+            .issues
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(
+            issues,
+            vec!["owner column team_long_name has dtype str, but owner ids are integers"]
+        );
+
+        plan.owner_columns.clear();
+        let report = dataset.validate(&Plan::Join(plan));
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.issues[0]
+            .message
+            .ends_with("declares no owner columns"));
+    }
+}